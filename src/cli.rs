@@ -23,7 +23,17 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// Find repos on search roots declared in the config
-    Search,
+    Search {
+        /// Print only the number of repos found
+        #[arg(long)]
+        count: bool,
+        /// Separate the repos with NUL instead of newlines, like `find -print0`. For `fzf --read0`
+        #[arg(short = '0', long, conflicts_with = "count")]
+        null: bool,
+        /// Print aligned `name -- path` columns for reading instead of bare paths
+        #[arg(short, long, conflicts_with = "count")]
+        long: bool,
+    },
     /// List configured and/or active sessions
     List {
         #[command(flatten)]
@@ -36,7 +46,11 @@ pub enum Commands {
     },
     /// Create layouts in bulk from supplied paths from stdin('~' is supported). Duplicate file
     /// names will not be deduplicated as usual
-    CreateAll,
+    CreateAll {
+        /// Open all of the created layouts in a single editor afterwards
+        #[arg(long)]
+        edit_all: bool,
+    },
     /// Edit an existing layout
     Edit {
         /// Name of the layout to be edited
@@ -46,18 +60,118 @@ pub enum Commands {
     Switch {
         #[command(flatten)]
         target: CliSwitchTarget,
+        /// Run the layout without attaching to the session, overrides `no_attach` in the config
+        #[arg(long)]
+        no_attach: bool,
+        /// Client to switch, e.g. its tty, instead of the current one
+        #[arg(long, env = "CELERIS_TMUX_CLIENT")]
+        client: Option<String>,
+    },
+    /// Run layouts without attaching, so that their sessions are ready to switch to. Layouts with
+    /// a running session are skipped
+    Warm {
+        /// Names of the layouts, all of them if omitted
+        names: Vec<String>,
     },
     /// Remove a layout
     Remove {
         /// Name/s of the layout/s to be removed
         names: Vec<String>,
     },
+    /// Print the name of the session the client is attached to, nothing when outside of tmux.
+    /// Doesn't look at the layouts, so it's cheap enough for a status bar
+    Current,
+    /// Kill the tmux server on the configured socket along with all of its sessions. Meant for
+    /// cleaning up after scripts and CI using `CELERIS_TMUX_SOCKET_NAME`/`CELERIS_TMUX_SOCKET_PATH`
+    #[command(hide = true)]
+    KillServer {
+        /// Kill the server even if it's the default one
+        #[arg(long)]
+        force: bool,
+    },
+    /// Diagnose the environment celeris runs in(tmux, editor, directories, search roots)
+    Doctor,
+    /// Run a layout without touching tmux and print what it would build
+    Validate {
+        /// Name of the layout to be validated
+        name: String,
+        /// Print the tmux commands the layout would run as a shell script instead
+        #[arg(long)]
+        print_cmd: bool,
+    },
+    /// Manage the search roots in the main config
+    Root {
+        #[command(subcommand)]
+        command: RootCommands,
+    },
+    /// Manage the main config
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RootCommands {
+    /// Add a search root, the path has to be an existing directory
+    Add {
+        /// Path of the directory the search should start from
+        path: PathBuf,
+        /// Depth of the search for this root, overrides the global one
+        #[arg(short, long)]
+        depth: Option<usize>,
+        /// Directory names excluded from the search for this root, can be supplied multiple times
+        #[arg(short, long)]
+        exclude: Vec<String>,
+        /// Template for layouts created under this root, relative to the config directory
+        #[arg(short, long)]
+        template: Option<String>,
+        /// Prefix of the names of repos under this root, e.g. `work` names them `work:<repo>`
+        #[arg(long)]
+        prefix: Option<String>,
+    },
+    /// Remove a search root
+    Remove {
+        /// Path of the search root as it appears in the config or one resolving to it
+        path: PathBuf,
+    },
+    /// List the search roots
+    List,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Set an option in the main config, comments and the rest of the file are preserved
+    Set {
+        /// Name of the option e.g. depth
+        key: String,
+        /// New value in toml syntax, plain text is treated as a string
+        value: String,
+    },
+    /// Print an option of the main config, nothing if it isn't set in it
+    Get {
+        /// Name of the option e.g. depth
+        key: String,
+    },
+    /// Print the location of the main config
+    Path,
+    /// Open the main config in $EDITOR
+    Edit,
 }
 
 #[derive(Args)]
 pub struct CreateOptions {
-    /// Root path of a session. By default the name is deduced automatically
-    path: PathBuf,
+    /// Root path of a session, the current directory if omitted. By default the name is deduced
+    /// automatically
+    path: Option<PathBuf>,
+    /// Use the repo found by search with this directory name, or path as printed by search, as the
+    /// root
+    #[arg(long, conflicts_with = "path")]
+    pub from_repo: Option<String>,
+    /// Use the current path of the active tmux pane as the root, which can differ from the current
+    /// directory e.g. when run from within another program. The current directory outside of tmux
+    #[arg(long, conflicts_with_all = ["path", "from_repo"])]
+    pub pane_cwd: bool,
     /// Set custom name for a layout
     #[arg(short, long)]
     name: Option<String>,
@@ -67,15 +181,32 @@ pub struct CreateOptions {
     /// Print the name of the layout created in a machine readable format
     #[arg(short, long)]
     machine_readable: bool,
+    /// Fail instead of warning when a running session already has the name of the layout
+    #[arg(short, long)]
+    strict: bool,
+    /// Leave the layout empty instead of filling it from a template
+    #[arg(
+        long,
+        visible_alias = "no-template",
+        conflicts_with = "template_default"
+    )]
+    empty: bool,
+    /// Use the built-in template even if a custom one exists
+    #[arg(long)]
+    template_default: bool,
 }
 
 impl From<CreateOptions> for CreateSessionOptions {
     fn from(value: CreateOptions) -> Self {
         Self {
-            path: value.path,
+            // canonicalized into the current directory later on
+            path: value.path.unwrap_or_else(|| PathBuf::from(".")),
             name: value.name,
             disable_editor: value.disable_editor,
             machine_readable: value.machine_readable,
+            strict: value.strict,
+            empty: value.empty,
+            default_template: value.template_default,
         }
     }
 }
@@ -86,15 +217,26 @@ pub struct CliSwitchTarget {
     /// Switch to the last loaded layout. Name mustn't be supplied when this flag is passed
     #[arg(short, long)]
     last_session: bool,
+    /// Switch to the session for a directory, the layout is created from the template if it
+    /// doesn't exist yet. The name is deduced from the directory name
+    #[arg(short, long)]
+    path: Option<PathBuf>,
     /// Name of the running session/predefined layout to switch into
     name: Option<String>,
 }
 
-impl From<CliSwitchTarget> for SwitchTarget {
+// A directory is switched to through its own method, since its layout may have to be created
+pub enum CliSwitch {
+    Target(SwitchTarget),
+    Path(PathBuf),
+}
+
+impl From<CliSwitchTarget> for CliSwitch {
     fn from(value: CliSwitchTarget) -> Self {
-        match value.last_session {
-            true => SwitchTarget::LastSession,
-            false => SwitchTarget::Session(value.name.unwrap()),
+        match (value.last_session, value.path) {
+            (true, _) => CliSwitch::Target(SwitchTarget::LastSession),
+            (false, Some(path)) => CliSwitch::Path(path),
+            (false, None) => CliSwitch::Target(SwitchTarget::Session(value.name.unwrap())),
         }
     }
 }
@@ -105,6 +247,14 @@ pub struct ListSessionsOptions {
     #[arg(short, long)]
     tmux_format: bool,
 
+    /// Print only the number of sessions, the other options still filter what's counted
+    #[arg(long)]
+    count: bool,
+
+    /// Separate the sessions with NUL instead of newlines, like `find -print0`. For `fzf --read0`
+    #[arg(short = '0', long, conflicts_with_all = ["tmux_format", "count"])]
+    null: bool,
+
     #[command(flatten)]
     conflicting: ListSessionsConflicting,
 }
@@ -116,6 +266,8 @@ impl Into<MgrListSessionsOptions> for ListSessionsOptions {
             include_active: self.conflicting.include_active,
             exclude_running: self.conflicting.exclude_running,
             only_running: self.conflicting.only_running,
+            count: self.count,
+            null: self.null,
         }
     }
 }
@@ -1,46 +1,201 @@
 use crate::directory_manager::DirectoryManager;
+use crate::layout;
+use crate::utils;
 use color_eyre::eyre::Context;
+use color_eyre::owo_colors::OwoColorize;
 use color_eyre::{Result, eyre};
 use eyre::eyre;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use toml_edit::{ArrayOfTables, DocumentMut, Item};
+
+// Options which can be read and changed with `Config::get`/`Config::set`, the version is managed
+// by celeris itself
+const CONFIG_KEYS: &[&str] = &[
+    "editor",
+    "editor_blocking",
+    "depth",
+    "search_subdirs",
+    "search_roots",
+    "excludes",
+    "disable_template",
+    "nested_layouts",
+    "layout_log",
+    "recent_first",
+    "strict_cache",
+    "rank",
+    "use_zoxide",
+    "zoxide_path",
+    "max_results",
+    "no_attach",
+    "name_style",
+];
+
+/// Bumped whenever the config changes in a way that's worth rewriting old config files for
+pub const CONFIG_VERSION: u32 = 1;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(default)]
 pub struct Config {
+    // configs written before versioning was introduced don't have it, hence 0 and not the default
+    #[serde(default)]
+    pub version: u32,
     pub editor: Option<String>,
+    /// Wait for the editor to exit. Turned off celeris returns right away, unless the editor is
+    /// passed `--wait`
+    pub editor_blocking: bool,
     pub depth: usize,
+    /// Descend into repos to find the ones nested in them. A search root which is a repo is
+    /// always a result itself, what's inside it is only searched with this set
     pub search_subdirs: bool,
     pub search_roots: Vec<SearchRoot>,
     pub excludes: Vec<String>,
     pub disable_template: bool,
+    pub nested_layouts: bool,
+    pub layout_log: Option<PathBuf>,
+    pub recent_first: bool,
+    pub strict_cache: bool,
+    pub rank: bool,
+    pub use_zoxide: bool,
+    /// The zoxide binary, looked up in `PATH` when unset
+    pub zoxide_path: Option<PathBuf>,
+    pub max_results: Option<usize>,
+    /// Layouts are run without attaching, `session:attach()` does nothing, and switching to a
+    /// running session only records it as the last one
+    pub no_attach: bool,
+    pub name_style: NameStyle,
+}
+
+/// How repos are named in the search results and in the names of layouts created for them
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NameStyle {
+    /// The directory name, same-named repos are told apart by their parent directories
+    #[default]
+    Basename,
+    /// The path relative to the search root the repo was found under
+    Relative,
+    /// The whole path, with the home directory shortened to `~`
+    Full,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             editor: None,
+            editor_blocking: true,
             depth: 10,
             search_subdirs: false,
             search_roots: Vec::new(),
             excludes: Vec::new(),
             disable_template: false,
+            nested_layouts: false,
+            layout_log: None,
+            recent_first: false,
+            strict_cache: false,
+            rank: false,
+            use_zoxide: false,
+            zoxide_path: None,
+            max_results: None,
+            no_attach: false,
+            name_style: NameStyle::default(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// Project-local overrides, every field that is absent keeps the value of the global config. Only
+// options which shape the layouts and the search are allowed, a cloned repo shouldn't get to pick
+// the editor that's run or the files that are written
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct PartialConfig {
+    depth: Option<usize>,
+    search_subdirs: Option<bool>,
+    excludes: Option<Vec<String>>,
+    disable_template: Option<bool>,
+    recent_first: Option<bool>,
+    rank: Option<bool>,
+    max_results: Option<usize>,
+    name_style: Option<NameStyle>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SearchRoot {
     pub path: String,
     pub depth: Option<usize>,
     pub excludes: Option<Vec<String>>,
+    /// Template used instead of the global one for layouts created under this root. Relative
+    /// paths are resolved against the config directory
+    pub template: Option<String>,
+    /// Repos under this root go by `prefix:name`, instead of being told apart by their parent
+    /// directories when the names collide
+    pub prefix: Option<String>,
+}
+
+impl SearchRoot {
+    pub fn validate(&self) -> Result<()> {
+        let root_path = Path::new(&self.path);
+        if !root_path.exists() {
+            return Err(eyre!("path not found: {}", self.path.clone()));
+        } else if !root_path.is_dir() {
+            return Err(eyre!("path is not a directory: {}", self.path.clone()));
+        }
+
+        Ok(())
+    }
 }
 
 impl Config {
     pub fn new(dir_mgr: &DirectoryManager) -> Result<Self> {
-        const CONFIG_FILE: &'static str = "config.toml";
-        let config_path = dir_mgr.config_dir().join(CONFIG_FILE);
+        let config = Self::load(dir_mgr)?;
+        Self::validate_config(&config)?;
+        Ok(config)
+    }
+
+    /// Loads the global config and overlays the nearest `.celeris.toml` found in `cwd` or its
+    /// ancestors on top of it
+    pub fn load_layered(dir_mgr: &DirectoryManager, cwd: &Path) -> Result<Self> {
+        let config = Self::load(dir_mgr)?;
+        let config = match Self::find_local(cwd) {
+            Some(local_path) => {
+                let local = fs::read_to_string(&local_path)
+                    .wrap_err_with(|| format!("failed to read local config: {local_path:?}"))?;
+                let local: PartialConfig = toml::from_str(&local)
+                    .wrap_err_with(|| format!("parsing error in local config: {local_path:?}"))?;
+                config.merge(local)
+            }
+            None => config,
+        };
+        Self::validate_config(&config)?;
+        Ok(config)
+    }
+
+    fn find_local(cwd: &Path) -> Option<PathBuf> {
+        const LOCAL_CONFIG_FILE: &str = ".celeris.toml";
+        cwd.ancestors()
+            .map(|dir| dir.join(LOCAL_CONFIG_FILE))
+            .find(|path| path.is_file())
+    }
+
+    fn merge(self, local: PartialConfig) -> Self {
+        Self {
+            depth: local.depth.unwrap_or(self.depth),
+            search_subdirs: local.search_subdirs.unwrap_or(self.search_subdirs),
+            excludes: local.excludes.unwrap_or(self.excludes),
+            disable_template: local.disable_template.unwrap_or(self.disable_template),
+            recent_first: local.recent_first.unwrap_or(self.recent_first),
+            rank: local.rank.unwrap_or(self.rank),
+            max_results: local.max_results.or(self.max_results),
+            name_style: local.name_style.unwrap_or(self.name_style),
+            ..self
+        }
+    }
+
+    /// Reads the main config without validating it, so that a broken one can still be inspected
+    pub fn load(dir_mgr: &DirectoryManager) -> Result<Self> {
+        let config_path = Self::path(dir_mgr);
 
         if !config_path.exists() {
             let config = Config::default();
@@ -54,24 +209,266 @@ impl Config {
         let config = fs::read_to_string(&config_path).wrap_err(format!(
             "failed to read main celeris config: {config_path:?}"
         ))?;
-        let config: Config = toml::from_str(&config).wrap_err("parsing error")?;
-        Self::validate_config(&config)?;
+        match toml::from_str(&config) {
+            Ok(parsed) => Self::migrate(parsed, &config, &config_path),
+            Err(e) => {
+                // kept around in case the config gets overwritten while trying to fix it
+                let backup_path = config_path.with_extension("toml.bak");
+                fs::copy(&config_path, &backup_path).wrap_err_with(|| {
+                    format!("failed to back up unparsable config to: {backup_path:?}")
+                })?;
+                // the toml error carries the line and column
+                Err(eyre!(
+                    "failed to parse {config_path:?}, a backup was saved to {backup_path:?}\n{e}"
+                ))
+            }
+        }
+    }
+
+    /// Reads a config file as it is, without writing, backing up or migrating anything. A missing
+    /// file is the default config
+    pub fn parse(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let config = fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read celeris config: {path:?}"))?;
+        toml::from_str(&config).map_err(|e| eyre!("failed to parse {path:?}\n{e}"))
+    }
+
+    // Rewrites an outdated config with the current version, user values and comments are kept
+    // and new fields get their defaults. The old file is still backed up just in case
+    fn migrate(mut config: Config, raw: &str, config_path: &Path) -> Result<Self> {
+        if config.version > CONFIG_VERSION {
+            eprintln!(
+                "{}: config version: {} is newer than the supported: {CONFIG_VERSION}, some options may be ignored",
+                "warning".yellow().bold(),
+                config.version
+            );
+            return Ok(config);
+        }
+        if config.version == CONFIG_VERSION {
+            return Ok(config);
+        }
+
+        let backup_path = config_path.with_extension(format!("toml.v{}.bak", config.version));
+        fs::copy(config_path, &backup_path)
+            .wrap_err_with(|| format!("failed to back up config to: {backup_path:?}"))?;
+        let mut document = raw
+            .parse::<DocumentMut>()
+            .wrap_err_with(|| format!("failed to parse config for migration: {config_path:?}"))?;
+        document["version"] = toml_edit::value(i64::from(CONFIG_VERSION));
+        let defaults = toml::to_string_pretty(&Config::default())
+            .wrap_err("failed to serialize default config")?
+            .parse::<DocumentMut>()
+            .wrap_err("failed to parse default config")?;
+        for (key, item) in defaults.iter() {
+            if !document.contains_key(key) {
+                document.insert(key, item.clone());
+            }
+        }
+        fs::write(config_path, document.to_string())
+            .wrap_err_with(|| format!("failed to write migrated config to: {config_path:?}"))?;
+
+        eprintln!(
+            "{}: migrated config from version {} to {CONFIG_VERSION}, the old one was saved to {backup_path:?}",
+            "info".green().bold(),
+            config.version
+        );
+        config.version = CONFIG_VERSION;
         Ok(config)
     }
 
+    /// Sets a single top-level option in the main config file. The rest of the file, including
+    /// comments and ordering, is left untouched. The value is parsed as toml and treated as a
+    /// string if that fails, so `nvim` and `"nvim"` are equivalent
+    pub fn set(dir_mgr: &DirectoryManager, key: &str, value: &str) -> Result<()> {
+        Self::check_key(key)?;
+        Self::edit_document(dir_mgr, |document| {
+            let mut new_value = value
+                .parse::<toml_edit::Value>()
+                .unwrap_or_else(|_| toml_edit::Value::from(value));
+            // keeps the comment trailing the old value
+            if let Some(old_value) = document.get(key).and_then(Item::as_value) {
+                *new_value.decor_mut() = old_value.decor().clone();
+            }
+            document[key] = Item::Value(new_value);
+            Ok(())
+        })
+        .wrap_err_with(|| format!("failed to set {key} to: {value}"))
+    }
+
+    /// Appends a search root to the main config. The path has to be an existing directory and
+    /// is stored canonicalized
+    pub fn add_search_root(dir_mgr: &DirectoryManager, mut root: SearchRoot) -> Result<()> {
+        root.path = utils::path_to_string(&utils::expand_path(Path::new(&root.path))?)?;
+        root.validate()?;
+        let new_root = toml::to_string(&root)
+            .wrap_err("failed to serialize search root")?
+            .parse::<DocumentMut>()
+            .wrap_err("failed to parse serialized search root")?
+            .as_table()
+            .clone();
+
+        Self::edit_document(dir_mgr, |document| {
+            if Self::search_root_paths(document).contains(&root.path.as_str()) {
+                return Err(eyre!("search root already exists: {}", root.path));
+            }
+            // both the [[search_roots]] and the inline array syntax are valid, the default config
+            // has an empty inline one which is replaced since it wouldn't stay readable for long
+            match document.get_mut("search_roots") {
+                None => {
+                    document["search_roots"] =
+                        Item::ArrayOfTables(ArrayOfTables::from_iter([new_root]));
+                }
+                Some(Item::Value(toml_edit::Value::Array(roots))) if roots.is_empty() => {
+                    document.remove("search_roots");
+                    document["search_roots"] =
+                        Item::ArrayOfTables(ArrayOfTables::from_iter([new_root]));
+                }
+                Some(Item::ArrayOfTables(roots)) => roots.push(new_root),
+                Some(Item::Value(toml_edit::Value::Array(roots))) => {
+                    roots.push(new_root.into_inline_table())
+                }
+                Some(_) => return Err(eyre!("search_roots is not an array")),
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes the search root with this path from the main config. The path is matched as is
+    /// and canonicalized, so that roots which don't exist anymore can be removed too
+    pub fn remove_search_root(dir_mgr: &DirectoryManager, path: &Path) -> Result<()> {
+        let candidates = [
+            Some(utils::path_to_string(path)?),
+            utils::expand_path(path)
+                .and_then(|path| utils::path_to_string(&path))
+                .ok(),
+        ];
+        let matches = |root_path: Option<&str>| {
+            candidates
+                .iter()
+                .flatten()
+                .any(|candidate| Some(candidate.as_str()) == root_path)
+        };
+
+        Self::edit_document(dir_mgr, |document| {
+            let removed = match document.get_mut("search_roots") {
+                Some(Item::ArrayOfTables(roots)) => {
+                    let before = roots.len();
+                    roots.retain(|root| !matches(root.get("path").and_then(Item::as_str)));
+                    before - roots.len()
+                }
+                Some(Item::Value(toml_edit::Value::Array(roots))) => {
+                    let before = roots.len();
+                    roots.retain(|root| {
+                        !matches(
+                            root.as_inline_table()
+                                .and_then(|root| root.get("path"))
+                                .and_then(toml_edit::Value::as_str),
+                        )
+                    });
+                    before - roots.len()
+                }
+                _ => 0,
+            };
+            match removed {
+                0 => Err(eyre!("search root not found: {path:?}")),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    fn search_root_paths(document: &DocumentMut) -> Vec<&str> {
+        match document.get("search_roots") {
+            Some(Item::ArrayOfTables(roots)) => roots
+                .iter()
+                .filter_map(|root| root.get("path")?.as_str())
+                .collect(),
+            Some(Item::Value(toml_edit::Value::Array(roots))) => roots
+                .iter()
+                .filter_map(|root| root.as_inline_table()?.get("path")?.as_str())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Applies an edit to the main config file while keeping its formatting. The result has to
+    // still parse as a config, otherwise nothing is written. An outdated config is edited as it
+    // is, it only gets migrated on startup
+    fn edit_document(
+        dir_mgr: &DirectoryManager,
+        edit: impl FnOnce(&mut DocumentMut) -> Result<()>,
+    ) -> Result<()> {
+        let config_path = Self::path(dir_mgr);
+        let config = match config_path.exists() {
+            true => fs::read_to_string(&config_path)
+                .wrap_err_with(|| format!("failed to read main celeris config: {config_path:?}"))?,
+            false => toml::to_string_pretty(&Config::default())
+                .wrap_err("failed to serialize default config")?,
+        };
+        let mut document = config
+            .parse::<DocumentMut>()
+            .wrap_err_with(|| format!("failed to parse main celeris config: {config_path:?}"))?;
+
+        edit(&mut document)?;
+        let config = document.to_string();
+        toml::from_str::<Config>(&config).wrap_err("edited config is invalid")?;
+        fs::write(&config_path, config)
+            .wrap_err_with(|| format!("failed to write to: {config_path:?}"))
+    }
+
+    /// Returns a single top-level option of the main config, strings as is and anything else in
+    /// toml syntax. Options which aren't set in the file return `None`, even if they have a default
+    pub fn get(dir_mgr: &DirectoryManager, key: &str) -> Result<Option<String>> {
+        Self::check_key(key)?;
+        let config_path = Self::path(dir_mgr);
+        if !config_path.exists() {
+            return Ok(None);
+        }
+        let config = fs::read_to_string(&config_path)
+            .wrap_err_with(|| format!("failed to read main celeris config: {config_path:?}"))?
+            .parse::<toml::Table>()
+            .wrap_err_with(|| format!("failed to parse main celeris config: {config_path:?}"))?;
+        Ok(config.get(key).map(|value| match value {
+            toml::Value::String(value) => value.clone(),
+            value => value.to_string(),
+        }))
+    }
+
+    /// Opens the main config in the editor, the default one is written first if it's missing
+    pub fn edit(dir_mgr: &DirectoryManager) -> Result<()> {
+        let config_path = Self::path(dir_mgr);
+        // a broken config is likely the reason it's being edited, so the editor from it is
+        // optional
+        let config = match config_path.exists() {
+            true => Self::parse(&config_path).unwrap_or_default(),
+            false => Self::load(dir_mgr)?,
+        };
+        layout::open_in_editor(&config, [&config_path])?;
+        Ok(())
+    }
+
+    /// Location of the main config file
+    pub fn path(dir_mgr: &DirectoryManager) -> PathBuf {
+        const CONFIG_FILE: &str = "config.toml";
+        dir_mgr.config_dir().join(CONFIG_FILE)
+    }
+
+    fn check_key(key: &str) -> Result<()> {
+        if !CONFIG_KEYS.contains(&key) {
+            return Err(eyre!(
+                "unknown config option: {key}, expected one of: {}",
+                CONFIG_KEYS.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
     fn validate_config(&self) -> Result<()> {
         self.search_roots
             .iter()
-            .map(|root| {
-                let root_path = Path::new(&root.path);
-                if !root_path.exists() {
-                    return Err(eyre!("path not found: {}", root.path.clone()));
-                } else if !root_path.is_dir() {
-                    return Err(eyre!("path is not a directory: {}", root.path.clone()));
-                }
-
-                Ok(())
-            })
+            .map(SearchRoot::validate)
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(())
@@ -30,6 +30,12 @@ impl DirectoryManagerBuilder {
         Ok(())
     }
 
+    fn create_path(path: &Path, directory_type: &str) -> Result<()> {
+        fs::create_dir_all(path).wrap_err_with(|| {
+            format!("failed to create custom {directory_type} directory: {path:?}")
+        })
+    }
+
     pub fn config_dir(&mut self, path: PathBuf) -> Result<&mut Self> {
         Self::check_path(&path, "config")?;
         self.custom_config_path = Some(path);
@@ -42,6 +48,20 @@ impl DirectoryManagerBuilder {
         Ok(self)
     }
 
+    /// Same as [`Self::config_dir`] but creates the directory(and its parents) if it's missing
+    pub fn config_dir_create(&mut self, path: PathBuf) -> Result<&mut Self> {
+        Self::create_path(&path, "config")?;
+        self.custom_config_path = Some(path);
+        Ok(self)
+    }
+
+    /// Same as [`Self::cache_dir`] but creates the directory(and its parents) if it's missing
+    pub fn cache_dir_create(&mut self, path: PathBuf) -> Result<&mut Self> {
+        Self::create_path(&path, "cache")?;
+        self.custom_cache_path = Some(path);
+        Ok(self)
+    }
+
     pub fn build(&mut self) -> Result<DirectoryManager> {
         DirectoryManager::try_new(
             self.custom_config_path.take(),
@@ -0,0 +1,261 @@
+use crate::config::Config;
+use crate::directory_manager::DirectoryManager;
+use crate::layout;
+use crate::tmux::{TmuxExecuteExt, tmux};
+use color_eyre::owo_colors::OwoColorize;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+// split-window -l with a percentage needs at least this version
+const MIN_TMUX_VERSION: (u32, u32) = (3, 1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Status {
+    Pass(String),
+    Warn { message: String, hint: String },
+    Fail { message: String, hint: String },
+}
+
+#[derive(Debug, Clone)]
+struct Check {
+    name: String,
+    status: Status,
+}
+
+impl Check {
+    fn pass(name: &str, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_owned(),
+            status: Status::Pass(message.into()),
+        }
+    }
+
+    fn warn(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name: name.to_owned(),
+            status: Status::Warn {
+                message: message.into(),
+                hint: hint.into(),
+            },
+        }
+    }
+
+    fn fail(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name: name.to_owned(),
+            status: Status::Fail {
+                message: message.into(),
+                hint: hint.into(),
+            },
+        }
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.status {
+            Status::Pass(message) => {
+                writeln!(f, "[{}] {}: {message}", "ok".green().bold(), self.name)
+            }
+            Status::Warn { message, hint } => {
+                writeln!(f, "[{}] {}: {message}", "warn".yellow().bold(), self.name)?;
+                writeln!(f, "       hint: {hint}")
+            }
+            Status::Fail { message, hint } => {
+                writeln!(f, "[{}] {}: {message}", "fail".red().bold(), self.name)?;
+                writeln!(f, "       hint: {hint}")
+            }
+        }
+    }
+}
+
+/// Result of the environment diagnostics run by [`doctor`]
+#[derive(Debug, Clone)]
+pub struct DoctorReport {
+    checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Returns true if any of the checks failed in a way that will break celeris
+    pub fn has_critical_failures(&self) -> bool {
+        self.checks
+            .iter()
+            .any(|check| matches!(check.status, Status::Fail { .. }))
+    }
+}
+
+impl Display for DoctorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.checks
+            .iter()
+            .try_for_each(|check| write!(f, "{check}"))
+    }
+}
+
+/// Checks the environment celeris runs in and gathers the results into a report
+pub fn doctor(dir_mgr: &DirectoryManager) -> DoctorReport {
+    let mut checks = vec![check_tmux()];
+    checks.extend([
+        check_dir("config directory", dir_mgr.config_dir()),
+        check_dir("cache directory", dir_mgr.cache_dir()),
+        check_dir("layouts directory", dir_mgr.layouts_dir()),
+    ]);
+
+    // only looked at, a broken or outdated config is backed up and migrated on the next start
+    let config_path = Config::path(dir_mgr);
+    match Config::parse(&config_path) {
+        Ok(config) => {
+            checks.push(check_editor(&config));
+            checks.extend(check_search_roots(&config));
+        }
+        Err(e) => checks.push(Check::fail(
+            "config",
+            format!("{e:#}"),
+            format!("fix or remove {config_path:?}"),
+        )),
+    }
+
+    DoctorReport { checks }
+}
+
+fn check_tmux() -> Check {
+    const NAME: &str = "tmux";
+    let output = match tmux().and_then(|mut command| command.arg("-V").execute()) {
+        Ok(output) => output,
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("failed to run tmux: {e}"),
+                "install tmux and make sure it's in $PATH",
+            );
+        }
+    };
+
+    let version = output.trim();
+    match parse_tmux_version(version) {
+        Some(parsed) if parsed >= MIN_TMUX_VERSION => Check::pass(NAME, version),
+        Some(_) => Check::fail(
+            NAME,
+            format!("{version} is too old"),
+            format!(
+                "upgrade tmux to at least {}.{}",
+                MIN_TMUX_VERSION.0, MIN_TMUX_VERSION.1
+            ),
+        ),
+        None => Check::warn(
+            NAME,
+            format!("couldn't determine the version of tmux: {version}"),
+            format!(
+                "celeris requires tmux {}.{} or newer",
+                MIN_TMUX_VERSION.0, MIN_TMUX_VERSION.1
+            ),
+        ),
+    }
+}
+
+// tmux -V prints things like "tmux 3.4", "tmux 3.3a" or "tmux next-3.5"
+fn parse_tmux_version(output: &str) -> Option<(u32, u32)> {
+    let version = output.split_whitespace().nth(1)?;
+    let version = version.strip_prefix("next-").unwrap_or(version);
+    let (major, minor) = version.split_once('.')?;
+    let minor = minor
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>();
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+fn check_dir(name: &str, path: &Path) -> Check {
+    if !path.is_dir() {
+        return Check::fail(
+            name,
+            format!("{path:?} doesn't exist or is not a directory"),
+            "create the directory or point celeris elsewhere with -c/--config-dir or -a/--cache-dir",
+        );
+    }
+
+    // permission bits lie in a lot of cases, actually writing something is the only reliable way
+    let probe = path.join(".celeris_doctor");
+    match fs::write(&probe, "").and_then(|_| fs::remove_file(&probe)) {
+        Ok(_) => Check::pass(name, format!("{path:?}")),
+        Err(e) => Check::fail(
+            name,
+            format!("{path:?} is not writable: {e}"),
+            "fix the permissions of the directory",
+        ),
+    }
+}
+
+fn check_editor(config: &Config) -> Check {
+    const NAME: &str = "editor";
+    match layout::resolve_editor(config) {
+        Ok(editor) => Check::pass(NAME, editor),
+        Err(e) => Check::warn(
+            NAME,
+            e.to_string(),
+            "set the `editor` option in config.toml, $VISUAL or $EDITOR",
+        ),
+    }
+}
+
+fn check_search_roots(config: &Config) -> Vec<Check> {
+    const NAME: &str = "search root";
+    if config.search_roots.is_empty() {
+        return vec![Check::warn(
+            NAME,
+            "no search roots defined, search will not find anything",
+            "add [[search_roots]] entries to config.toml",
+        )];
+    }
+
+    config
+        .search_roots
+        .iter()
+        .map(|root| match root.validate() {
+            Ok(_) => Check::pass(NAME, &root.path),
+            Err(e) => Check::fail(
+                NAME,
+                e.to_string(),
+                "fix or remove the search root in config.toml",
+            ),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn tmux_version() {
+        assert_eq!(parse_tmux_version("tmux 3.4"), Some((3, 4)));
+        assert_eq!(parse_tmux_version("tmux 3.3a"), Some((3, 3)));
+        assert_eq!(parse_tmux_version("tmux next-3.5"), Some((3, 5)));
+        assert_eq!(parse_tmux_version("tmux master"), None);
+        assert_eq!(parse_tmux_version(""), None);
+    }
+
+    #[test]
+    fn dir() {
+        let check = check_dir("temp", &env::temp_dir());
+        assert!(matches!(check.status, Status::Pass(_)));
+
+        let check = check_dir("missing", &env::temp_dir().join("__celeris_doctor_missing"));
+        assert!(matches!(check.status, Status::Fail { .. }));
+    }
+
+    #[test]
+    fn critical_failures() {
+        let report = DoctorReport {
+            checks: vec![Check::pass("a", "ok"), Check::warn("b", "meh", "hint")],
+        };
+        assert!(!report.has_critical_failures());
+
+        let report = DoctorReport {
+            checks: vec![Check::pass("a", "ok"), Check::fail("b", "bad", "hint")],
+        };
+        assert!(report.has_critical_failures());
+    }
+}
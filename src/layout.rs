@@ -1,25 +1,29 @@
 mod core;
 
+use color_eyre::owo_colors::OwoColorize;
 use core::ExtractLayoutsIterator;
 use delegate::delegate;
-use handlebars::{Handlebars, RenderError};
+use handlebars::{Handlebars, RenderError, handlebars_helper};
 use itertools::Itertools;
 use ref_cast::RefCast;
 use serde::Serialize;
-use std::env::VarError;
-use std::ffi::OsString;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
 use std::fmt::Display;
+use std::io::{self, Write};
+use std::iter;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::string;
 use std::sync::Arc;
-use std::{env, io};
 use std::{error, fs};
 use walkdir::WalkDir;
 
 use crate::config::Config;
 use crate::directory_manager::DirectoryManager;
-use crate::layout::core::{PathState, editor_decision};
+use crate::layout::core::{PathState, StorageMode, editor_decision};
+use crate::utils;
 use core::EditorDecision;
 use core::TemplateDecision;
 
@@ -31,9 +35,10 @@ pub enum Error {
     InvalidDirEntry(Box<dyn error::Error + Send + Sync + 'static>),
     NotFound(String),
     EditorNotFound,
-    EditorInvalid(OsString),
+    EditorInvalid(&'static str, OsString),
     TemplateRenderError(String, RenderError),
     InvalidUnicode(string::FromUtf8Error),
+    CorruptRoots(PathBuf, String),
 }
 
 impl Display for Error {
@@ -46,14 +51,20 @@ impl Display for Error {
             Self::InvalidDirEntry(_) => "invalid dir entry".to_owned(),
             Self::NotFound(layout) => format!("layout not found: {layout}"),
             Self::FailedCommand(command, _) => format!("failed to execute command: {command}"),
-            Self::EditorNotFound => "$EDITOR is not set nor set in the config".to_owned(),
-            Self::EditorInvalid(invalid_text) => {
-                format!("$EDITOR contains invalid unicode: {invalid_text:?}")
+            Self::EditorNotFound => {
+                "no editor found: set it in the config, $VISUAL or $EDITOR, or install vi"
+                    .to_owned()
+            }
+            Self::EditorInvalid(var, invalid_text) => {
+                format!("${var} contains invalid unicode: {invalid_text:?}")
             }
             Self::TemplateRenderError(comment, _) => {
                 format!("Failed to render layout template: {comment}")
             }
             Self::InvalidUnicode(_) => format!("encountered invalid unicode during processing"),
+            Self::CorruptRoots(path, comment) => {
+                format!("corrupt layout roots: {path:?}, {comment}")
+            }
         };
         write!(f, "{message}")
     }
@@ -88,6 +99,10 @@ impl From<string::FromUtf8Error> for Error {
 #[derive(Clone)]
 pub struct CreateLayoutOptions {
     pub disable_editor: bool,
+    /// Leave the layout empty regardless of the config
+    pub empty: bool,
+    /// Use the built-in template even if there is a custom one
+    pub default_template: bool,
 }
 
 pub struct LayoutName {
@@ -111,9 +126,15 @@ impl LayoutName {
         let core = core::LayoutName::try_from_path(path, path_state, &layout_manager.core)?;
         Ok(Self { core })
     }
+
+    delegate! {
+        to core::LayoutName {
+            pub fn tmux_safe(name: &str) -> String;
+        }
+    }
 }
 
-#[derive(Clone, Debug, RefCast)]
+#[derive(Clone, Debug, PartialEq, RefCast)]
 #[repr(transparent)]
 pub struct Layout {
     core: core::Layout,
@@ -123,18 +144,15 @@ impl Layout {
     delegate! {
         to self.core {
             pub fn tmux_name(&self) -> &str;
-            pub fn storage_path(&self, layouts_path: &Path) -> PathBuf;
         }
 
         to core::Layout {
             fn extension() -> OsString;
         }
     }
-}
 
-impl PartialEq for Layout {
-    fn eq(&self, other: &Self) -> bool {
-        self.core == other.core
+    pub fn storage_path(&self, layouts_path: &Path) -> Result<PathBuf, Error> {
+        Ok(self.core.storage_path(layouts_path)?)
     }
 }
 
@@ -154,14 +172,28 @@ pub struct LayoutManager {
 
 impl LayoutManager {
     const LAYOUT_CACHE: &'static str = "last_session";
+    const ROOTS_CACHE: &'static str = "layout_roots.toml";
 
-    pub fn enumerate_layouts(layouts_dir: &Path) -> Result<Vec<core::Layout>, Error> {
+    // Entries which are skipped get reported to `warnings`
+    pub fn enumerate_layouts(
+        layouts_dir: &Path,
+        warnings: &mut dyn Write,
+    ) -> Result<Vec<core::Layout>, Error> {
         let paths: Vec<PathBuf> = WalkDir::new(layouts_dir)
             .into_iter()
-            .map(|entry| -> Result<_, Error> {
-                Ok(entry
-                    .map_err(|e| Error::InvalidDirEntry(e.into()))?
-                    .into_path())
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(Ok(entry.into_path())),
+                // without the layouts directory itself there's nothing to list
+                Err(e) if e.depth() == 0 => Some(Err(Error::InvalidDirEntry(e.into()))),
+                // like ls, one unreadable entry shouldn't hide the rest
+                Err(e) => {
+                    let _ = writeln!(
+                        warnings,
+                        "{}: skipping layout entry: {e}",
+                        "warning".yellow().bold()
+                    );
+                    None
+                }
             })
             .try_collect()?;
 
@@ -172,6 +204,8 @@ impl LayoutManager {
                     true => PathState::File,
                     false => PathState::Directory,
                 };
+                // walkdir only yields paths inside of the root
+                let path = path.strip_prefix(layouts_dir).unwrap_or(&path).to_owned();
                 core::LayoutInfo::new(path, path_state)
             })
             .extract_layouts()
@@ -179,8 +213,12 @@ impl LayoutManager {
     }
 
     pub fn new(config: Arc<Config>, dir_mgr: Arc<DirectoryManager>) -> Result<Self, Error> {
-        let layouts = Self::enumerate_layouts(dir_mgr.layouts_dir())?;
-        let core = core::LayoutManager::new(layouts);
+        let layouts = Self::enumerate_layouts(dir_mgr.layouts_dir(), &mut io::stderr())?;
+        let storage = match config.nested_layouts {
+            true => StorageMode::Nested,
+            false => StorageMode::Flat,
+        };
+        let core = core::LayoutManager::new(layouts, storage);
         Ok(Self {
             core,
             config,
@@ -205,25 +243,43 @@ impl LayoutManager {
         let layout_name = layout.tmux_name().to_owned();
         let template = template(
             TemplateData::new(&layout_name, &root),
+            &opts,
             &self.config,
             self.dir_mgr.config_dir(),
         )?;
-        let layout_path = layout.storage_path(self.dir_mgr.layouts_dir());
+        let layout_path = layout.storage_path(self.dir_mgr.layouts_dir())?;
         self.core.create(layout.core)?;
 
+        if let Some(parent) = layout_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                Error::FSOperationFaiure(format!("failed to create directory: {parent:?}"), e)
+            })?;
+        }
         fs::write(layout_path, template).map_err(|e| {
             Error::FSOperationFaiure(
                 format!("failed to create layout with tmux_name: {}", layout_name),
                 e,
             )
         })?;
+        let mut roots = self.roots()?;
+        roots.insert(
+            layout_name.clone(),
+            root.canonicalize().unwrap_or_else(|_| root.to_owned()),
+        );
+        self.save_roots(&roots)?;
         if let EditorDecision::Spawn = editor_decision(opts.disable_editor) {
             self.edit(&layout_name)?;
         }
         Ok(())
     }
 
-    pub fn create_all(&mut self, rooted_layouts: Vec<(Layout, PathBuf)>) -> Result<(), Error> {
+    /// Creates all of the layouts, calling `progress` with a "created N/M: <name>" message after
+    /// each one. Returns the names of the created layouts
+    pub fn create_all(
+        &mut self,
+        rooted_layouts: Vec<(Layout, PathBuf)>,
+        mut progress: impl FnMut(&str),
+    ) -> Result<Vec<String>, Error> {
         let layouts = rooted_layouts
             .iter()
             .map(|(layout, _)| &layout.core)
@@ -231,12 +287,20 @@ impl LayoutManager {
         self.core.validate_layouts(layouts)?;
         let opts = CreateLayoutOptions {
             disable_editor: true,
+            empty: false,
+            default_template: false,
         };
+        let total = rooted_layouts.len();
         rooted_layouts
             .into_iter()
-            .map(|(layout, root)| self.create(layout, &root, opts.clone()))
-            .collect::<Result<(), Error>>()?;
-        Ok(())
+            .enumerate()
+            .map(|(i, (layout, root))| -> Result<String, Error> {
+                let name = layout.tmux_name().to_owned();
+                self.create(layout, &root, opts.clone())?;
+                progress(&format!("created {}/{total}: {name}", i + 1));
+                Ok(name)
+            })
+            .collect()
     }
 
     pub fn layout(&self, tmux_name: &str) -> Option<&Layout> {
@@ -245,7 +309,8 @@ impl LayoutManager {
 
     fn remove(&mut self, layout: &Layout) -> Result<(), Error> {
         self.core.remove(&layout.core)?;
-        fs::remove_file(layout.storage_path(self.dir_mgr.layouts_dir())).map_err(|e| {
+        let layout_path = layout.storage_path(self.dir_mgr.layouts_dir())?;
+        fs::remove_file(&layout_path).map_err(|e| {
             Error::FSOperationFaiure(
                 format!(
                     "failed to remove layout file with name: {}",
@@ -254,9 +319,24 @@ impl LayoutManager {
                 e,
             )
         })?;
+        self.remove_empty_parents(&layout_path);
+        let mut roots = self.roots()?;
+        if roots.remove(layout.tmux_name()).is_some() {
+            self.save_roots(&roots)?;
+        }
         Ok(())
     }
 
+    // Nested layouts leave their directories behind, best effort since it's only cosmetic
+    fn remove_empty_parents(&self, layout_path: &Path) {
+        let layouts_dir = self.dir_mgr.layouts_dir();
+        let _ = layout_path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| *dir != layouts_dir && dir.starts_with(layouts_dir))
+            .try_for_each(fs::remove_dir);
+    }
+
     pub fn remove_all(&mut self, layouts: Vec<&Layout>) -> Result<(), Error> {
         let layouts_core = layouts.iter().map(|l| &l.core).collect_vec();
         core::LayoutManager::check_duplicates(&layouts_core)?;
@@ -267,24 +347,20 @@ impl LayoutManager {
     }
 
     pub fn edit(&self, tmux_name: &str) -> Result<(), Error> {
-        let editor = self
-            .config
-            .editor
-            .clone()
-            .unwrap_or(env::var("EDITOR").map_err(|e| match e {
-                VarError::NotPresent => Error::EditorNotFound,
-                VarError::NotUnicode(invalid_text) => Error::EditorInvalid(invalid_text),
-            })?);
-
-        let layout = self
-            .layout(tmux_name)
-            .ok_or(Error::NotFound(tmux_name.to_owned()))?;
-        let layout_path = layout.storage_path(self.dir_mgr.layouts_dir());
-        Command::new(&editor)
-            .arg(layout_path)
-            .status()
-            .map_err(|e| Error::FailedCommand(editor, e))?;
-        Ok(())
+        self.edit_all(&[tmux_name])
+    }
+
+    /// Opens all of the layouts in a single editor process
+    pub fn edit_all(&self, tmux_names: &[&str]) -> Result<(), Error> {
+        let layout_paths = tmux_names
+            .iter()
+            .map(|name| {
+                self.layout(name)
+                    .ok_or(Error::NotFound((*name).to_owned()))?
+                    .storage_path(self.dir_mgr.layouts_dir())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        open_in_editor(&self.config, layout_paths)
     }
 
     pub fn save_if_layout(&self, name: &str) -> Result<(), Error> {
@@ -298,6 +374,31 @@ impl LayoutManager {
         Ok(())
     }
 
+    /// The directory the layout was created for. Unknown for layouts written by hand or created
+    /// before the roots were recorded
+    pub fn root(&self, tmux_name: &str) -> Result<Option<PathBuf>, Error> {
+        Ok(self.roots()?.remove(tmux_name))
+    }
+
+    fn roots(&self) -> Result<BTreeMap<String, PathBuf>, Error> {
+        let roots_path = self.dir_mgr.cache_dir().join(Self::ROOTS_CACHE);
+        if !roots_path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let roots = fs::read_to_string(&roots_path).map_err(|e| {
+            Error::FSOperationFaiure("failed to read the layout roots".to_owned(), e)
+        })?;
+        toml::from_str(&roots).map_err(|e| Error::CorruptRoots(roots_path, e.to_string()))
+    }
+
+    fn save_roots(&self, roots: &BTreeMap<String, PathBuf>) -> Result<(), Error> {
+        let roots_path = self.dir_mgr.cache_dir().join(Self::ROOTS_CACHE);
+        let serialized = toml::to_string(roots)
+            .map_err(|e| Error::CorruptRoots(roots_path.clone(), e.to_string()))?;
+        fs::write(roots_path, serialized)
+            .map_err(|e| Error::FSOperationFaiure("failed to save the layout roots".to_owned(), e))
+    }
+
     pub fn get_last(&self) -> Result<Option<String>, Error> {
         let last_session_path = self.dir_mgr.cache_dir().join(Self::LAYOUT_CACHE);
         if !last_session_path.exists() {
@@ -309,6 +410,71 @@ impl LayoutManager {
     }
 }
 
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+// config -> $VISUAL -> $EDITOR -> vi
+pub fn resolve_editor(config: &Config) -> Result<String, Error> {
+    resolve_editor_with(config, env::var_os)
+}
+
+// The environment is passed in, so that tests don't have to change the one of the whole process
+fn resolve_editor_with(
+    config: &Config,
+    var_os: impl Fn(&'static str) -> Option<OsString>,
+) -> Result<String, Error> {
+    const DEFAULT_EDITOR: &str = "vi";
+    let editor_var = |var| match var_os(var) {
+        Some(editor) => editor
+            .into_string()
+            .map(Some)
+            .map_err(|invalid_text| Error::EditorInvalid(var, invalid_text)),
+        None => Ok(None),
+    };
+    let default = || Ok(in_path(DEFAULT_EDITOR).then(|| DEFAULT_EDITOR.to_owned()));
+    // lazy, only the editors up to the one which is used have to be readable
+    let candidates = iter::once(Ok(config.editor.clone()))
+        .chain(["VISUAL", "EDITOR"].into_iter().map(editor_var))
+        .chain(iter::once_with(default));
+    core::editor_chain(candidates)?.ok_or(Error::EditorNotFound)
+}
+
+/// The resolved editor along with a command running it. The editor is split following shell quoting
+/// rules, so it can carry arguments e.g. `code --wait`, unless it names an existing file
+pub fn editor_command(config: &Config) -> Result<(String, Command), Error> {
+    let editor = resolve_editor(config)?;
+    let words = core::editor_words(&editor);
+    // the chain skips blank editors, so there is always a first word
+    let (program, args) = words
+        .split_first()
+        .map_or(("", &[][..]), |(p, a)| (p.as_str(), a));
+    let mut command = Command::new(program);
+    command.args(args);
+    Ok((editor, command))
+}
+
+/// Opens the paths in the editor, waiting for it to exit if it blocks according to the config. Known
+/// gui editors get their wait flag appended when blocking
+pub fn open_in_editor(
+    config: &Config,
+    paths: impl IntoIterator<Item = impl AsRef<OsStr>>,
+) -> Result<(), Error> {
+    let (editor, mut command) = editor_command(config)?;
+    // otherwise e.g. `create --switch` would carry on while the layout is still being edited
+    if let Some(flag) = core::wait_flag(config.editor_blocking, &editor) {
+        command.arg(flag);
+    }
+    command.args(paths);
+    let result = match core::editor_blocks(config.editor_blocking, &editor) {
+        true => command.status().map(|_| ()),
+        // e.g. a gui editor which would keep celeris around for no reason
+        false => command.spawn().map(|_| ()),
+    };
+    result.map_err(|e| Error::FailedCommand(editor, e))
+}
+
 #[derive(Serialize)]
 pub struct TemplateData<'a> {
     session_root: &'a Path,
@@ -324,32 +490,155 @@ impl<'a> TemplateData<'a> {
     }
 }
 
-fn template(data: TemplateData, config: &Config, config_dir: &Path) -> Result<String, Error> {
-    let handlebars = Handlebars::new();
+handlebars_helper!(uppercase: |text: str| text.to_uppercase());
+handlebars_helper!(basename: |path: str| Path::new(path)
+    .file_name()
+    .map(|name| name.to_string_lossy().into_owned())
+    .unwrap_or_default());
+
+// `name` is what the template is referred to as in errors
+fn render(name: &str, template: &str, data: &TemplateData) -> Result<String, Error> {
+    let mut handlebars = Handlebars::new();
+    handlebars.register_helper("uppercase", Box::new(uppercase));
+    handlebars.register_helper("basename", Box::new(basename));
+    let failed = |position: Option<(usize, usize)>| match position {
+        Some((line, column)) => format!("{name}, line {line}, column {column}"),
+        None => name.to_owned(),
+    };
+
+    handlebars
+        .register_template_string(name, template)
+        .map_err(|e| Error::TemplateRenderError(failed(e.pos()), e.into()))?;
+    handlebars
+        .render(name, data)
+        .map_err(|e| Error::TemplateRenderError(failed(e.line_no.zip(e.column_no)), e))
+}
+
+// The template of the most specific search root the session root is under
+fn root_template(
+    config: &Config,
+    config_dir: &Path,
+    session_root: &Path,
+) -> Result<Option<PathBuf>, Error> {
+    let session_root = session_root
+        .canonicalize()
+        .unwrap_or_else(|_| session_root.to_owned());
+    config
+        .search_roots
+        .iter()
+        .filter_map(|root| {
+            let template = root.template.as_ref()?;
+            // a root which can't be resolved isn't searched either
+            let path = utils::expand_path(Path::new(&root.path)).ok()?;
+            let path = path.canonicalize().unwrap_or(path);
+            session_root.starts_with(&path).then_some((path, template))
+        })
+        .max_by_key(|(path, _)| path.components().count())
+        .map(|(_, template)| {
+            // relative to the config directory, not the current one. Joining an absolute path
+            // replaces the config directory
+            let template =
+                utils::expand_home(Path::new(template)).map_err(|e| Error::CoreError(e.into()))?;
+            Ok(config_dir.join(template))
+        })
+        .transpose()
+}
+
+fn template(
+    data: TemplateData,
+    opts: &CreateLayoutOptions,
+    config: &Config,
+    config_dir: &Path,
+) -> Result<String, Error> {
     let default_template = include_str!("../templates/default.lua");
-    let custom_template_path = config_dir
-        .join("template")
-        .with_extension(Layout::extension());
-    let custom_template = if custom_template_path.exists() {
-        let raw_custom_template = fs::read(custom_template_path).map_err(|e| {
-            Error::FSOperationFaiure("failed to read custom template file".to_owned(), e)
+    let root_template = root_template(config, config_dir, data.session_root)?;
+    let custom_exists = root_template.is_some();
+    let custom_template_path = root_template.unwrap_or_else(|| {
+        config_dir
+            .join("template")
+            .with_extension(Layout::extension())
+    });
+    // a template set for the root has to exist, unlike the global one which is optional
+    let custom_exists = custom_exists || custom_template_path.exists();
+    let custom_template = if custom_exists && !opts.default_template {
+        let raw_custom_template = fs::read(&custom_template_path).map_err(|e| {
+            Error::FSOperationFaiure(
+                format!("failed to read custom template file: {custom_template_path:?}"),
+                e,
+            )
         })?;
         Some(String::from_utf8(raw_custom_template)?)
     } else {
         None
     };
 
-    match core::template_decision(config.disable_template, custom_template.is_some()) {
+    let template_disabled = config.disable_template || opts.empty;
+    match core::template_decision(template_disabled, custom_template.is_some()) {
         TemplateDecision::LeaveEmpty => Ok(String::new()),
-        TemplateDecision::GenerateDefault => Ok(handlebars
-            .render_template(default_template, &data)
-            .map_err(|e| {
-                Error::TemplateRenderError("failed to render default layout template".to_owned(), e)
-            })?),
-        TemplateDecision::GenerateCustom => Ok(handlebars
-            .render_template(custom_template.as_ref().unwrap(), &data)
-            .map_err(|e| {
-                Error::TemplateRenderError("failed to render custom layout template".to_owned(), e)
-            })?),
+        TemplateDecision::GenerateDefault => {
+            render("the built-in template", default_template, &data)
+        }
+        TemplateDecision::GenerateCustom => render(
+            &custom_template_path.display().to_string(),
+            custom_template.as_ref().unwrap(),
+            &data,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::Result;
+    use std::os::unix::ffi::OsStringExt;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn resolve_editor_invalid_var() {
+        let invalid = || Some(OsString::from_vec(vec![0xff, 0xfe]));
+        let config = Config {
+            editor: Some("nano".to_owned()),
+            ..Config::default()
+        };
+        let editor = resolve_editor_with(&config, |var| match var {
+            "VISUAL" => invalid(),
+            _ => None,
+        });
+        assert_eq!(editor.ok().as_deref(), Some("nano"));
+
+        // only reported when it's the one which would be used
+        let editor = resolve_editor_with(&Config::default(), |var| match var {
+            "VISUAL" => invalid(),
+            _ => None,
+        });
+        assert!(matches!(editor, Err(Error::EditorInvalid("VISUAL", _))));
+    }
+
+    #[test]
+    fn unreadable_layout_entry() -> Result<()> {
+        let layouts_dir = env::temp_dir().join("__celeris_unreadable_layouts");
+        if layouts_dir.exists() {
+            fs::remove_dir_all(&layouts_dir)?;
+        }
+        let unreadable = layouts_dir.join("unreadable");
+        fs::create_dir_all(&unreadable)?;
+        fs::write(layouts_dir.join("test1.lua"), "")?;
+        fs::write(unreadable.join("test2.lua"), "")?;
+        fs::set_permissions(&unreadable, fs::Permissions::from_mode(0o000))?;
+        // permissions have no effect when running as root
+        let skipped = fs::read_dir(&unreadable).is_err();
+
+        let mut warnings = Vec::new();
+        let layouts = LayoutManager::enumerate_layouts(&layouts_dir, &mut warnings);
+        fs::set_permissions(&unreadable, fs::Permissions::from_mode(0o755))?;
+        let layouts = layouts?;
+        assert!(layouts.iter().any(|layout| layout.tmux_name() == "test1"));
+        let warnings = String::from_utf8(warnings)?;
+        assert_eq!(
+            warnings.contains("skipping layout entry"),
+            skipped,
+            "{warnings}"
+        );
+        Ok(())
     }
 }
@@ -5,7 +5,7 @@ use std::{
     ffi::OsString,
     fmt::Display,
     ops::ControlFlow,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
 #[derive(Debug)]
@@ -16,6 +16,19 @@ pub enum Error {
     InvalidLayoutName(String),
     NotADirectory(PathBuf),
     DuplicateLayoutSupplied(String),
+    Collision {
+        layout: String,
+        existing: String,
+        on: Collision,
+    },
+}
+
+// What two colliding layouts share
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Collision {
+    TmuxName,
+    // relative to the layouts directory
+    StoragePath(PathBuf),
 }
 
 pub enum PathState {
@@ -34,6 +47,13 @@ pub enum EditorDecision {
     DontSpawn,
 }
 
+// Flat stores `a/b` as `a.b.lua`, nested mirrors the hierarchy on disk as `a/b.lua`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum StorageMode {
+    Flat,
+    Nested,
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let message = match self {
@@ -51,6 +71,21 @@ impl Display for Error {
             Self::DuplicateLayoutSupplied(layout_name) => {
                 format!("duplicate layouts names: {layout_name}")
             }
+            Self::Collision {
+                layout,
+                existing,
+                on: Collision::TmuxName,
+            } => format!(
+                "layout: {layout} collides with the existing layout: {existing}, both would spawn a session with the same name"
+            ),
+            Self::Collision {
+                layout,
+                existing,
+                on: Collision::StoragePath(storage_path),
+            } => format!(
+                "layout: {layout} collides with the existing layout: {existing}, both would be stored in the same file: {}",
+                storage_path.display()
+            ),
         };
         write!(f, "{message}")
     }
@@ -65,27 +100,74 @@ impl std::error::Error for Error {
     }
 }
 
+/// Name of a layout which doubles as the name of the tmux session it spawns.
+///
+/// tmux silently replaces `.` and `:` in session names with `_`, so they're swapped for the
+/// lookalikes `·` and `꞉` instead. `@`, `$` and `%` are allowed anywhere but the start of the
+/// name, where tmux would interpret them as a window, session or pane id. `/` separates the
+/// components of names deduced from paths.
 #[derive(Debug)]
-pub struct LayoutName(String);
+pub struct LayoutName {
+    name: String,
+    storage_name: String,
+}
 
 impl LayoutName {
     const STORAGE_NAME_DELIMETER: &str = ".";
     const TMUX_NAME_DELIMETER: &str = "/";
+    const TMUX_SUBSTITUTIONS: [(char, char); 2] = [('.', '·'), (':', '꞉')];
+    const TMUX_ID_PREFIXES: [char; 3] = ['@', '$', '%'];
+    // percent-encoded in storage names, so that the mapping from tmux names stays reversible
+    const STORAGE_ESCAPED: [char; 10] = ['%', '.', '\\', ':', '*', '?', '"', '<', '>', '|'];
 
     pub fn try_new(name: String, layout_manager: &LayoutManager) -> Result<Self, Error> {
+        let name = Self::tmux_safe(&name);
         if layout_manager.contains(&name) {
             return Err(Error::AlreadyExists(format!("layout named: {name}")));
         }
 
         Self::validate_name(&name)?;
-        Ok(Self(name))
+        Ok(Self {
+            storage_name: Self::encode_storage_path(&name, layout_manager.storage),
+            name,
+        })
+    }
+
+    // Maps a name the way it would be stored, so that the user can refer to `v1.2` as is
+    pub fn tmux_safe(name: &str) -> String {
+        name.chars()
+            .map(|c| {
+                Self::TMUX_SUBSTITUTIONS
+                    .iter()
+                    .find(|(special, _)| *special == c)
+                    .map_or(c, |(_, substitute)| *substitute)
+            })
+            .collect()
     }
 
     fn validate_name(name: &str) -> Result<(), Error> {
-        let tmux_special_chars = ['@', '$', '%', ':', '.'];
-        if name.chars().any(|c| tmux_special_chars.contains(&c)) {
+        if name.starts_with(Self::TMUX_ID_PREFIXES) {
+            return Err(Error::InvalidLayoutName(format!(
+                "name starts with a character that tmux treats as an id({:?}). You can also set a custom name when creating a session",
+                Self::TMUX_ID_PREFIXES
+            )));
+        }
+
+        // the components end up as directories in nested storage, where these would escape the
+        // layouts directory
+        if name
+            .split(Self::TMUX_NAME_DELIMETER)
+            .any(|component| ["", ".", ".."].contains(&component))
+        {
             return Err(Error::InvalidLayoutName(format!(
-                "name contains characters that tmux treats specially({tmux_special_chars:?}). You can also set a custom name when creating a session"
+                "name has an empty, `.` or `..` component: {name}"
+            )));
+        }
+
+        let storage_name = Self::encode_storage_name(name);
+        if sanitize_filename::sanitize(&storage_name) != storage_name {
+            return Err(Error::InvalidLayoutName(format!(
+                "name can't be used as a file name: {storage_name}"
             )));
         }
         Ok(())
@@ -122,35 +204,97 @@ impl LayoutName {
         Self::try_new(name, layout_manager)
     }
 
-    pub fn try_from_storage_name(storage_name: String) -> Result<Self, Error> {
-        let name = storage_name.replace(Self::STORAGE_NAME_DELIMETER, Self::TMUX_NAME_DELIMETER);
+    // Path relative to the layouts directory, every directory in it is a component of the name
+    pub fn try_from_storage_path(storage_path: &Path) -> Result<Self, Error> {
+        let storage_path = storage_path.with_extension("");
+        let components = storage_path
+            .components()
+            .map(|component| {
+                utils::path_to_string(Path::new(component.as_os_str()))
+                    .map_err(|e| Error::InvalidFilename(e.into()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let name = components
+            .iter()
+            .map(|component| Self::decode_storage_name(component))
+            .join(Self::TMUX_NAME_DELIMETER);
+        // files named by hand can contain anything, the same mapping as for new names applies
+        let name = Self::tmux_safe(&name);
         Self::validate_name(&name)?;
-        Ok(Self(name))
+        Ok(Self {
+            name,
+            // the layout might have been stored in a different mode, keep it where it is
+            storage_name: components.join(Self::TMUX_NAME_DELIMETER),
+        })
+    }
+
+    fn encode_storage_path(tmux_name: &str, storage: StorageMode) -> String {
+        match storage {
+            StorageMode::Flat => Self::encode_storage_name(tmux_name),
+            StorageMode::Nested => tmux_name
+                .split(Self::TMUX_NAME_DELIMETER)
+                .map(Self::encode_storage_name)
+                .join(Self::TMUX_NAME_DELIMETER),
+        }
+    }
+
+    fn encode_storage_name(tmux_name: &str) -> String {
+        tmux_name
+            .chars()
+            .map(|c| match c {
+                c if Self::TMUX_NAME_DELIMETER.starts_with(c) => {
+                    Self::STORAGE_NAME_DELIMETER.to_owned()
+                }
+                c if Self::STORAGE_ESCAPED.contains(&c)
+                    || (c.is_control() && (c as u32) <= 0xff) =>
+                {
+                    format!("%{:02X}", c as u32)
+                }
+                c => c.to_string(),
+            })
+            .collect()
+    }
+
+    // Malformed escapes are kept as is, so that files named by hand still load
+    fn decode_storage_name(storage_name: &str) -> String {
+        let mut name = String::with_capacity(storage_name.len());
+        let mut rest = storage_name;
+        while let Some(c) = rest.chars().next() {
+            let escaped = rest
+                .get(1..3)
+                .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match (c, escaped) {
+                ('%', Some(byte)) => {
+                    name.push(char::from(byte));
+                    rest = &rest[3..];
+                    continue;
+                }
+                (c, _) if Self::STORAGE_NAME_DELIMETER.starts_with(c) => {
+                    name.push_str(Self::TMUX_NAME_DELIMETER)
+                }
+                (c, _) => name.push(c),
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+        name
     }
 
     fn tmux_name(&self) -> &str {
-        &self.0
+        &self.name
     }
 
     fn storage_name(&self) -> String {
-        let storage_name = self
-            .0
-            .replace(Self::TMUX_NAME_DELIMETER, Self::STORAGE_NAME_DELIMETER);
-        sanitize_filename::sanitize(storage_name)
+        self.storage_name.clone()
     }
 }
 
-#[derive(Clone, Debug, Eq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Layout {
     tmux_name: String,
     storage_name: String,
 }
 
-impl PartialEq for Layout {
-    fn eq(&self, other: &Self) -> bool {
-        self.tmux_name == other.tmux_name || self.storage_name == other.tmux_name
-    }
-}
 impl Layout {
     pub fn new(layout_name: LayoutName) -> Self {
         Self {
@@ -159,12 +303,42 @@ impl Layout {
         }
     }
 
+    // Two layouts can't coexist if either one would shadow the other in tmux or on disk, unlike
+    // equality which needs both to match
+    pub fn collides_with(&self, other: &Self) -> bool {
+        self.collision(other).is_some()
+    }
+
+    pub fn collision(&self, other: &Self) -> Option<Collision> {
+        if self.tmux_name == other.tmux_name {
+            Some(Collision::TmuxName)
+        } else if self.storage_name == other.storage_name {
+            Some(Collision::StoragePath(self.relative_storage_path()))
+        } else {
+            None
+        }
+    }
+
     pub fn tmux_name(&self) -> &str {
         &self.tmux_name
     }
 
-    pub fn storage_path(&self, layouts_dir: &Path) -> PathBuf {
-        let path = layouts_dir.join(&self.storage_name);
+    pub fn storage_path(&self, layouts_dir: &Path) -> Result<PathBuf, Error> {
+        let path = self.relative_storage_path();
+        // anything other than plain components would point outside of the layouts directory
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(Error::InvalidLayoutName(format!(
+                "storage path is outside of the layouts directory: {path:?}"
+            )));
+        }
+        Ok(layouts_dir.join(path))
+    }
+
+    fn relative_storage_path(&self) -> PathBuf {
+        let path = PathBuf::from(&self.storage_name);
         // yeah it's ugly because add_extension is still in fucking nightly
         let final_extension = if path.extension().is_some() {
             let mut final_extension = path.extension().unwrap().to_owned();
@@ -209,10 +383,7 @@ impl<'a> ExtractLayouts<'a> {
                 PathState::File => true,
             })
             .filter(|info| info.path.extension() == Some(&Layout::extension()))
-            .map(|info| {
-                Ok(utils::file_stem(&info.path).map_err(|e| Error::InvalidFilename(e.into()))?)
-            })
-            .map(|filename| Ok(LayoutName::try_from_storage_name(filename?)?))
+            .map(|info| LayoutName::try_from_storage_path(&info.path))
             .map(|layout_name| Ok(Layout::new(layout_name?)));
         Self {
             iter: Box::new(iter),
@@ -238,20 +409,23 @@ impl<'a, I: Iterator<Item = LayoutInfo> + 'a> ExtractLayoutsIterator<'a> for I {
 #[derive(Debug)]
 pub struct LayoutManager {
     layouts: Vec<Layout>,
+    storage: StorageMode,
 }
 
 impl LayoutManager {
-    pub fn new(layouts: Vec<Layout>) -> Self {
-        Self { layouts }
+    pub fn new(layouts: Vec<Layout>, storage: StorageMode) -> Self {
+        Self { layouts, storage }
     }
 
     pub fn layout(&self, tmux_name: &str) -> Option<&Layout> {
+        let tmux_name = LayoutName::tmux_safe(tmux_name);
         self.layouts
             .iter()
             .find(|layout| layout.tmux_name == tmux_name)
     }
 
     pub fn contains(&self, tmux_name: &str) -> bool {
+        let tmux_name = LayoutName::tmux_safe(tmux_name);
         self.layouts
             .iter()
             .find(|s| s.tmux_name == tmux_name)
@@ -259,8 +433,11 @@ impl LayoutManager {
     }
 
     pub fn check_duplicates(layouts: &[&Layout]) -> Result<(), Error> {
-        let duplicate_within_set = layouts.iter().duplicates().nth(0);
-        if let Some(duplicate) = duplicate_within_set {
+        let duplicate_within_set = layouts
+            .iter()
+            .tuple_combinations()
+            .find(|(a, b)| a.collides_with(b));
+        if let Some((duplicate, _)) = duplicate_within_set {
             return Err(Error::DuplicateLayoutSupplied(
                 duplicate.tmux_name().to_owned(),
             ));
@@ -289,8 +466,16 @@ impl LayoutManager {
 
     // impure
     pub fn create(&mut self, layout: Layout) -> Result<(), Error> {
-        if self.layouts.contains(&layout) {
-            return Err(Error::AlreadyExists(layout.tmux_name));
+        let collision = self
+            .layouts
+            .iter()
+            .find_map(|existing| Some((existing, existing.collision(&layout)?)));
+        if let Some((existing, on)) = collision {
+            return Err(Error::Collision {
+                layout: layout.tmux_name,
+                existing: existing.tmux_name.clone(),
+                on,
+            });
         }
         self.layouts.push(layout);
         Ok(())
@@ -322,13 +507,72 @@ pub fn editor_decision(disable_editor_on_creation: bool) -> EditorDecision {
     }
 }
 
+// Splits the editor into the program and its arguments following shell quoting rules. An editor
+// naming an existing file is taken whole, so paths containing spaces work without quotes
+pub fn editor_words(editor: &str) -> Vec<String> {
+    if Path::new(editor).is_file() {
+        return vec![editor.to_owned()];
+    }
+    shlex::split(editor).unwrap_or_else(|| vec![editor.to_owned()])
+}
+
+// `--wait` is how gui editors are told to block, so it's honored regardless of the config
+pub fn editor_blocks(blocking: bool, editor: &str) -> bool {
+    blocking || editor_words(editor).iter().any(|word| word == "--wait")
+}
+
+// gui editors which return right away unless told to wait for the files to be closed
+const WAIT_FLAGS: [(&str, &str); 4] = [
+    ("code", "--wait"),
+    ("subl", "--wait"),
+    ("zed", "--wait"),
+    ("gedit", "--wait"),
+];
+
+// The flag to append so that a known gui editor blocks, if it should block and wasn't told to already
+pub fn wait_flag(blocking: bool, editor: &str) -> Option<&'static str> {
+    if !blocking {
+        return None;
+    }
+    let words = editor_words(editor);
+    let (program, args) = words.split_first()?;
+    let program = Path::new(program).file_name()?.to_str()?;
+    let (_, flag) = WAIT_FLAGS.iter().find(|(name, _)| *name == program)?;
+    match args.iter().any(|word| word == flag) {
+        true => None,
+        false => Some(flag),
+    }
+}
+
+// first editor that is set and isn't blank wins. Candidates are only looked at until then, so one
+// further down that fails to be read doesn't matter
+pub fn editor_chain<E>(
+    candidates: impl IntoIterator<Item = Result<Option<String>, E>>,
+) -> Result<Option<String>, E> {
+    for candidate in candidates {
+        if let Some(editor) = candidate?
+            && !editor.trim().is_empty()
+        {
+            return Ok(Some(editor));
+        }
+    }
+    Ok(None)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use color_eyre::Result;
 
+    fn flat_name(name: &str) -> LayoutName {
+        LayoutName {
+            name: name.to_owned(),
+            storage_name: LayoutName::encode_storage_path(name, StorageMode::Flat),
+        }
+    }
+
     fn test_layout(name: &str) -> Result<Layout> {
-        Ok(Layout::new(LayoutName(name.to_owned())))
+        Ok(Layout::new(flat_name(name)))
     }
 
     fn layout_manager_with_names(names: Vec<&'static str>) -> Result<LayoutManager> {
@@ -336,7 +580,7 @@ mod tests {
             .into_iter()
             .map(|name| test_layout(name))
             .collect::<Result<Vec<_>>>()?;
-        Ok(LayoutManager::new(layouts))
+        Ok(LayoutManager::new(layouts, StorageMode::Flat))
     }
 
     #[test]
@@ -359,7 +603,7 @@ mod tests {
         let storage_paths_got = layouts
             .iter()
             .map(|layout| layout.storage_path(&layout_dir))
-            .collect_vec();
+            .collect::<Result<Vec<_>, _>>()?;
 
         assert_eq!(expected_storage_paths, storage_paths_got);
         Ok(())
@@ -405,7 +649,279 @@ mod tests {
         fn duplicate() -> Result<()> {
             let mut layout_manager = layout_manager_with_names(vec!["test"])?;
             let result = layout_manager.create(test_layout("test")?);
-            assert_eq!(result.is_err(), true);
+            assert!(matches!(
+                result,
+                Err(Error::Collision {
+                    on: Collision::TmuxName,
+                    ..
+                })
+            ));
+            Ok(())
+        }
+
+        #[test]
+        fn storage_collision() {
+            let layout = |tmux_name: &str| Layout {
+                tmux_name: tmux_name.to_owned(),
+                storage_name: "shared".to_owned(),
+            };
+            let mut layout_manager = LayoutManager::new(vec![layout("a")], StorageMode::Flat);
+            let error = layout_manager.create(layout("b")).unwrap_err();
+            let Error::Collision {
+                layout: new,
+                existing,
+                on,
+            } = &error
+            else {
+                panic!("expected a collision, got: {error}");
+            };
+            assert_eq!((new.as_str(), existing.as_str()), ("b", "a"));
+            assert_eq!(*on, Collision::StoragePath(PathBuf::from("shared.lua")));
+            assert!(error.to_string().contains("shared.lua"), "{error}");
+            assert!(LayoutManager::check_duplicates(&[&layout("a"), &layout("b")]).is_err());
+        }
+
+        #[test]
+        fn nested_storage_collision() {
+            let layout = |tmux_name: &str| Layout {
+                tmux_name: tmux_name.to_owned(),
+                storage_name: "dir/shared.v2".to_owned(),
+            };
+            let mut layout_manager = LayoutManager::new(vec![layout("a")], StorageMode::Nested);
+            let error = layout_manager.create(layout("b")).unwrap_err();
+            assert!(
+                error
+                    .to_string()
+                    .ends_with("the same file: dir/shared.v2.lua"),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn equality() -> Result<()> {
+        let layout = test_layout("test")?;
+        assert_eq!(layout, test_layout("test")?);
+        let same_tmux_name = Layout {
+            storage_name: "other".to_owned(),
+            ..layout.clone()
+        };
+        assert_ne!(layout, same_tmux_name);
+        assert!(layout.collides_with(&same_tmux_name));
+        assert!(!layout.collides_with(&test_layout("other")?));
+        Ok(())
+    }
+
+    mod editor_chain {
+        use super::*;
+
+        fn chain(candidates: [Option<&str>; 4]) -> Option<String> {
+            editor_chain(candidates.map(|c| Ok::<_, ()>(c.map(ToOwned::to_owned))))
+                .expect("none of the candidates fail")
+        }
+
+        #[test]
+        fn config() {
+            let editor = chain([Some("nano"), Some("code"), Some("nvim"), Some("vi")]);
+            assert_eq!(editor.as_deref(), Some("nano"));
+        }
+
+        #[test]
+        fn visual() {
+            let editor = chain([None, Some("code"), Some("nvim"), Some("vi")]);
+            assert_eq!(editor.as_deref(), Some("code"));
+        }
+
+        #[test]
+        fn editor() {
+            let editor = chain([None, None, Some("nvim"), Some("vi")]);
+            assert_eq!(editor.as_deref(), Some("nvim"));
+        }
+
+        #[test]
+        fn default() {
+            let editor = chain([None, Some(""), None, Some("vi")]);
+            assert_eq!(editor.as_deref(), Some("vi"));
+        }
+
+        #[test]
+        fn not_found() {
+            assert_eq!(chain([None, Some(" "), None, None]), None);
+        }
+
+        #[test]
+        fn failing() {
+            let editor = editor_chain([Ok(Some("nano".to_owned())), Err("invalid")]);
+            assert_eq!(editor, Ok(Some("nano".to_owned())));
+            let editor = editor_chain([Ok(None), Err("invalid"), Ok(Some("vi".to_owned()))]);
+            assert_eq!(editor, Err("invalid"));
+        }
+
+        #[test]
+        fn words() -> Result<()> {
+            assert_eq!(editor_words("code --wait"), ["code", "--wait"]);
+            assert_eq!(
+                editor_words(r#""/opt/My Apps/subl" -n"#),
+                ["/opt/My Apps/subl", "-n"]
+            );
+            assert_eq!(
+                editor_words("vim -c 'set ft=lua'"),
+                ["vim", "-c", "set ft=lua"]
+            );
+            let dir = std::env::temp_dir().join("__celeris_editor words");
+            std::fs::create_dir_all(&dir)?;
+            let editor = dir.join("my editor");
+            std::fs::write(&editor, "")?;
+            let editor = editor.to_str().unwrap();
+            assert_eq!(editor_words(editor), [editor]);
+            std::fs::remove_dir_all(&dir)?;
+            Ok(())
+        }
+
+        #[test]
+        fn blocks() {
+            assert!(editor_blocks(true, "nvim"));
+            assert!(!editor_blocks(false, "code"));
+            assert!(editor_blocks(false, "code --wait"));
+            assert!(!editor_blocks(false, "code --waiting"));
+        }
+
+        #[test]
+        fn wait_flags() {
+            assert_eq!(wait_flag(true, "code"), Some("--wait"));
+            assert_eq!(wait_flag(true, "/usr/bin/subl -n"), Some("--wait"));
+            assert_eq!(wait_flag(true, "code --wait"), None);
+            assert_eq!(wait_flag(false, "code"), None);
+            assert_eq!(wait_flag(true, "nvim"), None);
+            assert_eq!(wait_flag(true, "vscode"), None);
+            assert_eq!(wait_flag(true, "'/opt/My Apps/code' -n"), Some("--wait"));
+        }
+    }
+
+    mod name {
+        use super::*;
+
+        #[test]
+        fn tmux_special_chars() -> Result<()> {
+            let layout_manager = layout_manager_with_names(Vec::new())?;
+            let name = LayoutName::try_new("v1.2:3".to_owned(), &layout_manager)?;
+            assert_eq!(name.tmux_name(), "v1·2꞉3");
+
+            let name = LayoutName::try_new("a@b$c%d".to_owned(), &layout_manager)?;
+            assert_eq!(name.tmux_name(), "a@b$c%d");
+
+            ["@a", "$a", "%a"].into_iter().for_each(|name| {
+                let _ = LayoutName::try_new(name.to_owned(), &layout_manager)
+                    .expect_err("names starting with a tmux id prefix should be rejected");
+            });
+            Ok(())
+        }
+
+        #[test]
+        fn lookup_unescaped() -> Result<()> {
+            let mut layout_manager = layout_manager_with_names(Vec::new())?;
+            let name = LayoutName::try_new("foo.nvim".to_owned(), &layout_manager)?;
+            layout_manager.create(Layout::new(name))?;
+            assert!(layout_manager.contains("foo.nvim"));
+            assert!(layout_manager.contains("foo·nvim"));
+            assert!(layout_manager.layout("foo.nvim").is_some());
+            Ok(())
+        }
+
+        #[test]
+        fn storage_encoding() {
+            let names = [
+                "a.b/c",
+                "a/b/c",
+                "a%2Eb",
+                "%",
+                "100%/x",
+                "trailing%",
+                "%zz",
+                "a?b*c:d",
+                "v1·2",
+                "back\\slash",
+            ];
+            names.into_iter().for_each(|name| {
+                let layout_name = flat_name(name);
+                let storage_name = layout_name.storage_name();
+                assert_eq!(
+                    LayoutName::decode_storage_name(&storage_name),
+                    name,
+                    "storage name: {storage_name}"
+                );
+                assert_eq!(sanitize_filename::sanitize(&storage_name), storage_name);
+            });
+            assert_eq!(flat_name("a.b/c").storage_name(), "a%2Eb.c");
+            // names stored before escaping existed still decode the same way
+            assert_eq!(LayoutName::decode_storage_name("test.aaa"), "test/aaa");
+        }
+
+        #[test]
+        fn storage_round_trip() -> Result<()> {
+            let layout_manager = layout_manager_with_names(Vec::new())?;
+            let name = LayoutName::try_new("v1.2/test".to_owned(), &layout_manager)?;
+            let layout = Layout::new(name);
+            let storage_path = layout.storage_path(Path::new(""))?;
+            let round_trip = LayoutName::try_from_storage_path(&storage_path)?;
+            assert_eq!(round_trip.tmux_name(), layout.tmux_name());
+            Ok(())
+        }
+    }
+
+    mod nested {
+        use super::*;
+
+        #[test]
+        fn storage_path() -> Result<()> {
+            let layout_manager = LayoutManager::new(Vec::new(), StorageMode::Nested);
+            let name = LayoutName::try_new("a.b/c".to_owned(), &layout_manager)?;
+            let layout = Layout::new(name);
+            assert_eq!(
+                layout.storage_path(Path::new("/test"))?,
+                Path::new("/test/a·b/c.lua")
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn from_storage_path() -> Result<()> {
+            let name = LayoutName::try_from_storage_path(Path::new("a/b%2Ec.lua"))?;
+            assert_eq!(name.tmux_name(), "a/b·c");
+            assert_eq!(name.storage_name(), "a/b%2Ec");
+
+            // mixed storage stays where it is
+            let name = LayoutName::try_from_storage_path(Path::new("a/b.c.lua"))?;
+            assert_eq!(name.tmux_name(), "a/b/c");
+            assert_eq!(name.storage_name(), "a/b.c");
+
+            let name = LayoutName::try_from_storage_path(Path::new("a.b.lua"))?;
+            assert_eq!(name.tmux_name(), "a/b");
+            assert_eq!(name.storage_name(), "a.b");
+            Ok(())
+        }
+
+        #[test]
+        fn outside_of_layouts_dir() -> Result<()> {
+            let layout_manager = LayoutManager::new(Vec::new(), StorageMode::Nested);
+            ["/tmp/evil", "a//b", "a/", "/", "a/./b", "a/../../b", ".."]
+                .into_iter()
+                .for_each(|name| {
+                    let _ = LayoutName::validate_name(name)
+                        .expect_err(&format!("name should be rejected: {name}"));
+                });
+            ["/tmp/evil", "a//b", "a/"].into_iter().for_each(|name| {
+                let _ = LayoutName::try_new(name.to_owned(), &layout_manager)
+                    .expect_err(&format!("name should be rejected: {name}"));
+            });
+
+            let layout = Layout::new(LayoutName {
+                name: "evil".to_owned(),
+                storage_name: "../evil".to_owned(),
+            });
+            let _ = layout
+                .storage_path(Path::new("/test"))
+                .expect_err("storage path outside of the layouts directory should be rejected");
             Ok(())
         }
     }
@@ -1,19 +1,51 @@
 mod config;
 mod directory_manager;
+mod doctor;
 mod layout;
 mod repo_search;
+#[cfg_attr(not(feature = "lua"), path = "script/disabled.rs")]
 mod script;
 mod session_manager;
 pub mod tmux;
 mod utils;
 
 #[doc(inline)]
-pub use config::{Config, SearchRoot};
+pub use config::{CONFIG_VERSION, Config, NameStyle, SearchRoot};
 #[doc(inline)]
 pub use directory_manager::{DirectoryManager, DirectoryManagerBuilder};
 #[doc(inline)]
-pub use repo_search::search;
+pub use doctor::{DoctorReport, doctor};
+#[doc(inline)]
+pub use layout::Error as LayoutError;
+#[cfg(feature = "async")]
+#[doc(inline)]
+pub use repo_search::{AsyncSearch, search_async};
+#[doc(inline)]
+pub use repo_search::{format_repos, search, search_streaming, search_with_recent};
 #[doc(inline)]
 pub use session_manager::{
-    CreateSessionOptions, ListSessionsOptions, SessionManager, SwitchTarget,
+    CreateSessionOptions, CreatedSession, Error, ListSessionsOptions, SessionManager, SwitchTarget,
+};
+#[doc(inline)]
+pub use utils::expand_glob;
+
+// The public types are meant to be usable from other threads e.g. an async runtime or a gui, this
+// fails to compile if one of them stops being Send + Sync
+const fn assert_send_sync<T: Send + Sync>() {}
+
+const _: () = {
+    assert_send_sync::<SessionManager>();
+    assert_send_sync::<Config>();
+    assert_send_sync::<DirectoryManager>();
+    assert_send_sync::<tmux::Session>();
+    assert_send_sync::<tmux::Window>();
+    assert_send_sync::<tmux::Pane>();
+    assert_send_sync::<tmux::SessionBuilder>();
+    assert_send_sync::<tmux::WindowBuilder>();
+    assert_send_sync::<tmux::SplitBuilder>();
+    assert_send_sync::<CreatedSession>();
+    assert_send_sync::<DoctorReport>();
+    assert_send_sync::<Error>();
+    #[cfg(feature = "async")]
+    assert_send_sync::<AsyncSearch>();
 };
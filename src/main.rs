@@ -1,11 +1,16 @@
 mod cli;
-use celeris::{Config, DirectoryManager, SessionManager};
+mod spinner;
+use celeris::tmux::{self, Session};
+use celeris::{Config, CreateSessionOptions, DirectoryManager, SearchRoot, SessionManager};
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, CliSwitch, Commands, ConfigCommands, RootCommands};
 use color_eyre::Result;
-use color_eyre::eyre::Context;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use color_eyre::eyre::{Context, eyre};
+use itertools::Itertools;
+use spinner::Spinner;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process;
 use std::sync::Arc;
 
 fn main() -> Result<()> {
@@ -13,6 +18,21 @@ fn main() -> Result<()> {
         .display_env_section(false)
         .install()?;
     let cli = Cli::parse();
+    // runs on every status bar redraw, so none of the directories, the config or the layouts are
+    // touched
+    if let Commands::Current = cli.command {
+        return write_output(&Session::active_name()?.unwrap_or_default());
+    }
+
+    if let Commands::KillServer { force } = cli.command {
+        if !force && !tmux::custom_socket() {
+            return Err(eyre!(
+                "refusing to kill the default tmux server, set CELERIS_TMUX_SOCKET_NAME or CELERIS_TMUX_SOCKET_PATH or pass --force"
+            ));
+        }
+        return Ok(tmux::kill_server()?);
+    }
+
     let mut dir_mgr_builder = DirectoryManager::builder();
     if let Some(config_dir) = cli.config_dir {
         dir_mgr_builder.config_dir(config_dir)?;
@@ -23,32 +43,146 @@ fn main() -> Result<()> {
     }
     let dir_mgr = dir_mgr_builder.build()?;
 
-    let config = Arc::new(Config::new(&dir_mgr)?);
-    let mut session_manager = SessionManager::new(Arc::clone(&config), Arc::new(dir_mgr))?;
+    // has to run before the config is loaded, since diagnosing a broken config is the point
+    if let Commands::Doctor = cli.command {
+        let report = celeris::doctor(&dir_mgr);
+        print!("{report}");
+        if report.has_critical_failures() {
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // skips validation, so that e.g. a search root which no longer exists can be fixed with it
+    if let Commands::Root { command } = cli.command {
+        match command {
+            RootCommands::Add {
+                path,
+                depth,
+                exclude,
+                template,
+                prefix,
+            } => {
+                let root = SearchRoot {
+                    path: path.to_string_lossy().into_owned(),
+                    depth,
+                    excludes: (!exclude.is_empty()).then_some(exclude),
+                    template,
+                    prefix,
+                };
+                Config::add_search_root(&dir_mgr, root)?
+            }
+            RootCommands::Remove { path } => Config::remove_search_root(&dir_mgr, &path)?,
+            RootCommands::List => {
+                let roots = Config::parse(&Config::path(&dir_mgr))?
+                    .search_roots
+                    .into_iter()
+                    .map(|root| root.path)
+                    .join("\n");
+                write_output(&roots)?
+            }
+        }
+        return Ok(());
+    }
+
+    if let Commands::Config { command } = cli.command {
+        match command {
+            ConfigCommands::Set { key, value } => Config::set(&dir_mgr, &key, &value)?,
+            ConfigCommands::Get { key } => {
+                write_output(&Config::get(&dir_mgr, &key)?.unwrap_or_default())?
+            }
+            ConfigCommands::Path => write_output(&Config::path(&dir_mgr).to_string_lossy())?,
+            ConfigCommands::Edit => Config::edit(&dir_mgr)?,
+        }
+        return Ok(());
+    }
+
+    let cwd = env::current_dir().wrap_err("failed to get the current directory")?;
+    let mut config = Config::load_layered(&dir_mgr, &cwd)?;
+    if let Commands::Switch {
+        no_attach: true, ..
+    } = cli.command
+    {
+        config.no_attach = true;
+    }
+    let config = Arc::new(config);
+    let dir_mgr = Arc::new(dir_mgr);
+    let mut session_manager = SessionManager::new(Arc::clone(&config), Arc::clone(&dir_mgr))?;
 
     match cli.command {
         Commands::Edit { name } => session_manager.edit(&name)?,
-        Commands::Switch { target } => session_manager.switch(target.into())?,
+        Commands::Switch { target, client, .. } => match target.into() {
+            CliSwitch::Target(target) => session_manager.switch(target, client.as_deref())?,
+            CliSwitch::Path(path) => session_manager.switch_path(&path, client.as_deref())?,
+        },
+        Commands::Warm { names } => session_manager.warm(names)?,
         Commands::Remove { names } => session_manager.remove(names)?,
-        Commands::Create { opts } => session_manager.create(opts.into())?,
-        Commands::CreateAll => {
+        Commands::Create { opts } => {
+            let from_repo = opts.from_repo.clone();
+            let pane_cwd = opts.pane_cwd;
+            let mut opts: CreateSessionOptions = opts.into();
+            if let Some(name) = from_repo {
+                opts.path = session_manager.find_repo(&name)?;
+            }
+            if pane_cwd {
+                // outside of tmux it stays the current directory
+                if let Some(path) = Session::active_pane_path()? {
+                    opts.path = path;
+                }
+            }
+            session_manager.create(opts)?;
+        }
+        Commands::CreateAll { edit_all } => {
             let paths = io::stdin()
                 .lines()
-                .map(|path| Ok(PathBuf::from(path?)))
+                .map(|line| celeris::expand_glob(&line?, &mut io::stderr()))
+                .flatten_ok()
                 .collect::<Result<Vec<_>>>()?;
-            session_manager.create_all(paths)?;
+            let verbose = io::stderr().is_terminal();
+            let created = session_manager.create_all(paths, |message| {
+                if verbose {
+                    eprintln!("{message}");
+                }
+            })?;
+            if edit_all && !created.is_empty() {
+                session_manager.edit_all(&created)?;
+            }
         }
         _ => {
             let output = match cli.command {
-                Commands::Search => celeris::search(&config)?.join("\n"),
+                Commands::Search { count, null, long } => {
+                    // a slow scan would otherwise look like it hung
+                    let spinner = io::stderr()
+                        .is_terminal()
+                        .then(|| Spinner::start("searching for repos".to_owned()));
+                    let repos = celeris::search_with_recent(&config, &dir_mgr)?;
+                    drop(spinner);
+                    let repos = match long {
+                        true => celeris::format_repos(&config, &repos),
+                        false => repos,
+                    };
+                    match (count, null) {
+                        (true, _) => repos.len().to_string(),
+                        (false, true) => repos.join("\0"),
+                        (false, false) => repos.join("\n"),
+                    }
+                }
                 Commands::List { opts } => session_manager.list(opts.into())?,
+                Commands::Validate { name, print_cmd } => match print_cmd {
+                    true => session_manager.print_commands(&name)?,
+                    false => session_manager.validate(&name)?,
+                },
                 _ => unreachable!(),
             };
 
-            io::stdout()
-                .write_all(output.as_bytes())
-                .wrap_err("failed to write result of subcommand to stdout")?
+            write_output(&output)?
         }
     }
     Ok(())
 }
+
+fn write_output(output: &str) -> Result<()> {
+    io::stdout()
+        .write_all(output.as_bytes())
+        .wrap_err("failed to write result of subcommand to stdout")
+}
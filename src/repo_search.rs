@@ -1,20 +1,259 @@
-use crate::{config::Config, utils};
+use crate::{
+    config::{Config, NameStyle},
+    directory_manager::DirectoryManager,
+    utils,
+};
 use color_eyre::Result;
+use color_eyre::eyre::{Context, eyre};
 use color_eyre::owo_colors::OwoColorize;
 use git2::Repository;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::iter;
 use std::path::{Path, PathBuf};
-use walkdir::{DirEntry, WalkDir};
+use std::process::Command;
+#[cfg(feature = "async")]
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "async")]
+use tokio::{sync::mpsc, task::JoinHandle};
+use walkdir::WalkDir;
 
+const RECENT_REPOS_CACHE: &str = "recent_repos";
+const MAX_RECENT_REPOS: usize = 50;
+const VISITED_REPOS_CACHE: &str = "visited_repos";
+const MAX_VISITED_REPOS: usize = 500;
+const ZOXIDE_DIRECTORIES: usize = 100;
+// widest a repo name gets in format_repos
+const MAX_NAME_WIDTH: usize = 32;
+
+/// Searches for repos in the search roots, in the order they're found. With `max_results` set the
+/// search stops once that many repos are found, so which ones are returned depends on the
+/// traversal order. `recent_first` and `rank` are ignored, see [`search_with_recent`]
 pub fn search(config: &Config) -> Result<Vec<String>> {
-    if config.search_roots.is_empty() {
-        eprintln!(
+    into_strings(find(config))
+}
+
+/// Like [`search`], but with `recent_first` set in the config repos which had a layout created
+/// recently come first, most recent at the top. `rank` orders them by how often and how recently
+/// they were picked instead and takes precedence. Both are read from the cache directory
+pub fn search_with_recent(config: &Config, dir_mgr: &DirectoryManager) -> Result<Vec<String>> {
+    let mut repos = find(config);
+
+    // the sorts are stable, so the rest stays in the order it was found in
+    if config.rank {
+        let visited = visited_repos(config, dir_mgr)?;
+        let now = unix_now();
+        let mut scored = repos
+            .into_iter()
+            .map(|repo| {
+                let canonical = repo.canonicalize().unwrap_or_else(|_| repo.clone());
+                let score = visited
+                    .iter()
+                    .find(|visited| visited.path == canonical)
+                    .map_or(0.0, |visited| frecency(visited.count, visited.last, now));
+                (score, repo)
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        repos = scored.into_iter().map(|(_, repo)| repo).collect();
+    } else if config.recent_first {
+        let recent = recent_repos(config, dir_mgr)?;
+        // canonicalizing is a syscall, so it's done once per repo instead of once per comparison
+        repos.sort_by_cached_key(|repo| {
+            let repo = repo.canonicalize().unwrap_or_else(|_| repo.clone());
+            recent
+                .iter()
+                .position(|recent| *recent == repo)
+                .unwrap_or(usize::MAX)
+        });
+    }
+
+    into_strings(repos)
+}
+
+fn find(config: &Config) -> Vec<PathBuf> {
+    let mut repos = Vec::new();
+    walk(config, &AtomicBool::new(false), &mut io::stderr(), |repo| {
+        repos.push(repo)
+    });
+    repos
+}
+
+fn into_strings(repos: Vec<PathBuf>) -> Result<Vec<String>> {
+    repos
+        .into_iter()
+        .map(utils::shorten_path)
+        .map(|p| utils::path_to_string(p.as_path()))
+        .collect()
+}
+
+/// The name of a repo in the configured [`NameStyle`], as `prefix:name` if the most specific
+/// search root it's under has a `prefix`. `None` for plain basenames, those are told apart by
+/// their parent directories instead
+pub(crate) fn repo_name(config: &Config, repo: &Path) -> Option<String> {
+    let repo = utils::expand_path(repo).unwrap_or_else(|_| repo.to_owned());
+    let root = config
+        .search_roots
+        .iter()
+        .filter_map(|root| {
+            // a root which can't be resolved isn't searched either
+            let path = utils::expand_path(Path::new(&root.path)).ok()?;
+            repo.starts_with(&path).then_some((path, root))
+        })
+        .max_by_key(|(path, _)| path.components().count());
+    let basename = repo.file_name()?.to_string_lossy().into_owned();
+    let name = match config.name_style {
+        NameStyle::Basename => basename,
+        NameStyle::Relative => match &root {
+            Some((path, _)) if repo != *path => repo
+                .strip_prefix(path)
+                .map_or(basename, |relative| relative.to_string_lossy().into_owned()),
+            // the root itself or a repo from zoxide
+            _ => basename,
+        },
+        NameStyle::Full => utils::shorten_path(repo.clone())
+            .to_string_lossy()
+            .into_owned(),
+    };
+    match root.and_then(|(_, root)| root.prefix.as_ref()) {
+        Some(prefix) => Some(format!("{prefix}:{name}")),
+        None if config.name_style == NameStyle::Basename => None,
+        None => Some(name),
+    }
+}
+
+/// Formats repos returned by [`search`] as `name -- path` lines, with the paths aligned for
+/// reading. Names longer than 32 characters are cut off with an ellipsis so that a single
+/// long one doesn't push all of the paths away
+pub fn format_repos(config: &Config, repos: &[String]) -> Vec<String> {
+    let names = repos
+        .iter()
+        .map(|repo| {
+            let name = repo_name(config, Path::new(repo)).unwrap_or_else(|| {
+                Path::new(repo)
+                    .file_name()
+                    .map_or_else(|| repo.clone(), |name| name.to_string_lossy().into_owned())
+            });
+            match name.chars().count() > MAX_NAME_WIDTH {
+                true => name.chars().take(MAX_NAME_WIDTH - 1).chain(['…']).collect(),
+                false => name,
+            }
+        })
+        .collect::<Vec<_>>();
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    names
+        .iter()
+        .zip(repos)
+        .map(|(name, repo)| format!("{name:<width$} -- {repo}"))
+        .collect()
+}
+
+/// Like [`search`], but hands over each repo as soon as it's found instead of waiting for the whole
+/// walk, e.g. to show results of a slow scan right away. `rank` and `recent_first` are ignored
+/// since ordering needs all of the results, repos come in the order they're found. Setting
+/// `cancelled`, from any thread, stops the search early. It's set on the first error as well, the
+/// rest of the walk would be thrown away anyway
+pub fn search_streaming(
+    config: &Config,
+    cancelled: &AtomicBool,
+    mut found: impl FnMut(String),
+) -> Result<()> {
+    let mut error = None;
+    walk(config, cancelled, &mut io::stderr(), |repo| {
+        if error.is_some() {
+            return;
+        }
+        match utils::path_to_string(&utils::shorten_path(repo)) {
+            Ok(repo) => found(repo),
+            Err(e) => {
+                error = Some(e);
+                cancelled.store(true, Ordering::Relaxed);
+            }
+        }
+    });
+    error.map_or(Ok(()), Err)
+}
+
+/// A search running in the background, started with [`search_async`]. Dropping it cancels the
+/// search
+#[cfg(feature = "async")]
+pub struct AsyncSearch {
+    repos: mpsc::UnboundedReceiver<String>,
+    walk: Option<JoinHandle<Result<()>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncSearch {
+    /// Waits for the next repo, `None` once the search is over
+    pub async fn next(&mut self) -> Option<String> {
+        self.repos.recv().await
+    }
+
+    /// Waits for the search to end, returning the error that stopped it if there was one
+    pub async fn finish(mut self) -> Result<()> {
+        let Some(walk) = self.walk.take() else {
+            return Ok(());
+        };
+        walk.await.wrap_err("search task failed")?
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for AsyncSearch {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Like [`search_streaming`], but runs the walk on the blocking thread pool of the current tokio
+/// runtime, so an event loop isn't held up by it. Panics outside of a tokio runtime
+#[cfg(feature = "async")]
+pub fn search_async(config: Config) -> AsyncSearch {
+    let (sender, repos) = mpsc::unbounded_channel();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let walk_cancelled = Arc::clone(&cancelled);
+    let walk = tokio::task::spawn_blocking(move || {
+        search_streaming(&config, &walk_cancelled, |repo| {
+            // nobody is listening anymore
+            if sender.send(repo).is_err() {
+                walk_cancelled.store(true, Ordering::Relaxed);
+            }
+        })
+    });
+    AsyncSearch {
+        repos,
+        walk: Some(walk),
+        cancelled,
+    }
+}
+
+// Warnings go to `warnings` instead of straight to stderr, so that tests can read them. Failing to
+// write one isn't a reason to stop the search
+fn walk(
+    config: &Config,
+    cancelled: &AtomicBool,
+    warnings: &mut dyn Write,
+    mut found: impl FnMut(PathBuf),
+) {
+    if config.search_roots.is_empty() && !config.use_zoxide {
+        let _ = writeln!(
+            warnings,
             "{}: search roots are not defined, nothing to search in",
             "warning".yellow().bold()
         );
-        return Ok(Vec::new());
+        return;
     }
 
-    let mut repos: Vec<PathBuf> = Vec::new();
+    let mut count = 0;
+    let mut seen = HashSet::new();
     // Side-effects were needed
     config.search_roots.iter().for_each(|root| {
         let local_excludes = root.excludes.clone().unwrap_or_default();
@@ -23,56 +262,363 @@ pub fn search(config: &Config) -> Result<Vec<String>> {
             .max_depth(root.depth.unwrap_or(config.depth))
             .into_iter()
             .filter_entry(|entry| {
-                if is_excluded_from(&config.excludes, entry)
-                    || is_excluded_from(&local_excludes, entry)
+                // walkdir can't be stopped, but pruning everything makes the rest of the walk
+                // cheap
+                if cancelled.load(Ordering::Relaxed)
+                    || config.max_results.is_some_and(|max| count >= max)
+                {
+                    return false;
+                }
+                if is_excluded_from(&config.excludes, entry.path())
+                    || is_excluded_from(&local_excludes, entry.path())
                 {
                     return false;
                 }
 
-                // There was no other way to do it using walkdir
-                repos.push_if_repo(entry);
-                config.search_subdirs || !is_repo(entry)
+                // There was no other way to do it using walkdir. The root goes through here as
+                // well, so a root which is a repo is a result and is only descended into with
+                // search_subdirs, like any other repo
+                let repo = is_repo(entry.path());
+                if repo {
+                    count += 1;
+                    seen.insert(entry.path().to_path_buf());
+                    found(entry.path().to_path_buf());
+                }
+                config.search_subdirs || !repo
+            })
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(entry),
+                // unreadable entries further down are expected e.g. someone else's home, but
+                // an unreadable root means the config points somewhere inaccessible
+                Err(e) if e.depth() == 0 => {
+                    let _ = writeln!(
+                        warnings,
+                        "{}: search root is unreadable, skipping it: {e}",
+                        "warning".yellow().bold()
+                    );
+                    None
+                }
+                Err(_) => None,
             })
-            .filter_map(|entry| entry.ok())
             .filter(|entry| entry.path().is_dir())
             .collect();
     });
 
-    Ok(repos
+    if !config.use_zoxide {
+        return;
+    }
+    for dir in zoxide_dirs(config, warnings) {
+        if cancelled.load(Ordering::Relaxed) || config.max_results.is_some_and(|max| count >= max) {
+            return;
+        }
+        // the search roots may have found it already
+        if seen.contains(&dir) || is_excluded_from(&config.excludes, &dir) || !is_repo(&dir) {
+            continue;
+        }
+        count += 1;
+        found(dir);
+    }
+}
+
+// zoxide lists the directories best ranked first. Not having it installed isn't fatal, the search
+// roots still get searched
+fn zoxide_dirs(config: &Config, warnings: &mut dyn Write) -> Vec<PathBuf> {
+    let zoxide = config.zoxide_path.as_deref().unwrap_or(Path::new("zoxide"));
+    let output = match Command::new(zoxide).args(["query", "--list"]).output() {
+        Ok(output) => output,
+        Err(e) => {
+            let _ = writeln!(
+                warnings,
+                "{}: failed to run zoxide, is it installed? {e}",
+                "warning".yellow().bold()
+            );
+            return Vec::new();
+        }
+    };
+    // an empty database makes it fail without saying anything
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() && !stderr.trim().is_empty() {
+        let _ = writeln!(
+            warnings,
+            "{}: zoxide failed: {}",
+            "warning".yellow().bold(),
+            stderr.trim()
+        );
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .take(ZOXIDE_DIRECTORIES)
+        .map(PathBuf::from)
+        .collect()
+}
+
+// A corrupt cache is backed up and started over unless `strict_cache` is set, since it's only an
+// aid to the search
+fn load_cache<T: Default>(
+    config: &Config,
+    cache_path: &Path,
+    read: impl FnOnce(&Path) -> Result<T>,
+) -> Result<T> {
+    if !cache_path.exists() {
+        return Ok(T::default());
+    }
+    match read(cache_path) {
+        Ok(cache) => Ok(cache),
+        Err(e) if config.strict_cache => Err(e),
+        Err(e) => {
+            let backup_path = cache_path.with_extension("bak");
+            fs::rename(cache_path, &backup_path)
+                .wrap_err_with(|| format!("failed to back up corrupt cache to: {backup_path:?}"))?;
+            eprintln!(
+                "{}: {e:#}, starting over. The old one was saved to {backup_path:?}",
+                "warning".yellow().bold()
+            );
+            Ok(T::default())
+        }
+    }
+}
+
+fn recent_repos(config: &Config, dir_mgr: &DirectoryManager) -> Result<Vec<PathBuf>> {
+    let recent_path = dir_mgr.cache_dir().join(RECENT_REPOS_CACHE);
+    load_cache(config, &recent_path, read_recent_repos)
+}
+
+fn read_recent_repos(recent_path: &Path) -> Result<Vec<PathBuf>> {
+    let recent = fs::read_to_string(recent_path)
+        .wrap_err_with(|| format!("failed to read recent repos: {recent_path:?}"))?;
+    recent
+        .lines()
+        .enumerate()
+        .map(|(i, line)| match Path::new(line).is_absolute() {
+            true => Ok(PathBuf::from(line)),
+            false => Err(eyre!(
+                "corrupt recent repos: {recent_path:?}, line {}: {line:?} is not an absolute path",
+                i + 1
+            )),
+        })
+        .collect()
+}
+
+// Moves the path to the top of the recently used repos, only the most recent ones are kept
+pub(crate) fn record_recent(
+    config: &Config,
+    dir_mgr: &DirectoryManager,
+    path: &Path,
+) -> Result<()> {
+    let recent_path = dir_mgr.cache_dir().join(RECENT_REPOS_CACHE);
+    let recent = iter::once(path.to_path_buf())
+        .chain(
+            recent_repos(config, dir_mgr)?
+                .into_iter()
+                .filter(|recent| recent != path),
+        )
+        .take(MAX_RECENT_REPOS)
+        .map(|recent| utils::path_to_string(&recent))
+        .collect::<Result<Vec<_>>>()?;
+    fs::write(&recent_path, recent.join("\n"))
+        .wrap_err_with(|| format!("failed to save recent repos: {recent_path:?}"))
+}
+
+struct VisitedRepo {
+    path: PathBuf,
+    count: u32,
+    // seconds since the unix epoch
+    last: u64,
+}
+
+fn visited_repos(config: &Config, dir_mgr: &DirectoryManager) -> Result<Vec<VisitedRepo>> {
+    let visited_path = dir_mgr.cache_dir().join(VISITED_REPOS_CACHE);
+    load_cache(config, &visited_path, read_visited_repos)
+}
+
+// One `<count>\t<last visit>\t<path>` line per repo, the path goes last since it may contain tabs
+fn read_visited_repos(visited_path: &Path) -> Result<Vec<VisitedRepo>> {
+    let visited = fs::read_to_string(visited_path)
+        .wrap_err_with(|| format!("failed to read visited repos: {visited_path:?}"))?;
+    visited
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let mut fields = line.splitn(3, '\t');
+            let count = fields.next().and_then(|count| count.parse().ok());
+            let last = fields.next().and_then(|last| last.parse().ok());
+            let path = fields.next().map(Path::new);
+            match (count, last, path) {
+                (Some(count), Some(last), Some(path)) if path.is_absolute() => Ok(VisitedRepo {
+                    path: path.to_path_buf(),
+                    count,
+                    last,
+                }),
+                _ => Err(eyre!(
+                    "corrupt visited repos: {visited_path:?}, line {}: {line:?}",
+                    i + 1
+                )),
+            }
+        })
+        .collect()
+}
+
+// Counts a visit to the repo for `rank`, only the highest scoring ones are kept
+pub(crate) fn record_visit(config: &Config, dir_mgr: &DirectoryManager, path: &Path) -> Result<()> {
+    let visited_path = dir_mgr.cache_dir().join(VISITED_REPOS_CACHE);
+    let now = unix_now();
+    let mut visited = visited_repos(config, dir_mgr)?;
+    match visited.iter_mut().find(|visited| visited.path == path) {
+        Some(visited) => {
+            visited.count = visited.count.saturating_add(1);
+            visited.last = now;
+        }
+        None => visited.push(VisitedRepo {
+            path: path.to_path_buf(),
+            count: 1,
+            last: now,
+        }),
+    }
+
+    visited
+        .sort_by(|a, b| frecency(b.count, b.last, now).total_cmp(&frecency(a.count, a.last, now)));
+    let visited = visited
         .into_iter()
-        .map(utils::shorten_path)
-        .map(|p| utils::path_to_string(p.as_path()))
-        .collect::<Result<Vec<_>>>()?)
+        .take(MAX_VISITED_REPOS)
+        .map(|visited| {
+            let path = utils::path_to_string(&visited.path)?;
+            Ok(format!("{}\t{}\t{path}", visited.count, visited.last))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    fs::write(&visited_path, visited.join("\n"))
+        .wrap_err_with(|| format!("failed to save visited repos: {visited_path:?}"))
+}
+
+// Scored like zoxide does it, the number of visits weighted by how long ago the last one was
+fn frecency(count: u32, last_visit: u64, now: u64) -> f64 {
+    const HOUR: u64 = 60 * 60;
+    let weight = match now.saturating_sub(last_visit) {
+        age if age < HOUR => 4.0,
+        age if age < 24 * HOUR => 2.0,
+        age if age < 7 * 24 * HOUR => 0.5,
+        _ => 0.25,
+    };
+    f64::from(count) * weight
+}
+
+fn unix_now() -> u64 {
+    // a clock set before the epoch only makes every visit look equally old
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
 }
 
-fn is_excluded_from(excludes: &Vec<String>, entry: &DirEntry) -> bool {
-    !excludes.iter().all(|exclude| !is_excluded(exclude, entry))
+fn is_excluded_from(excludes: &[String], path: &Path) -> bool {
+    !excludes.iter().all(|exclude| !is_excluded(exclude, path))
 }
 
-fn is_excluded(exclude: &str, entry: &DirEntry) -> bool {
+fn is_excluded(exclude: &str, path: &Path) -> bool {
     let exclude_path = Path::new(exclude);
     if exclude_path.is_absolute() {
-        exclude_path == entry.path()
+        exclude_path == path
     } else {
-        exclude == entry.file_name().to_str().unwrap_or_default()
+        exclude
+            == path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default()
     }
 }
 
-trait RepoPushExt {
-    fn push_if_repo(&mut self, entry: &DirEntry);
+fn is_repo(path: &Path) -> bool {
+    matches!(Repository::open(path), Ok(repo) if repo.workdir() == Some(path))
 }
 
-impl RepoPushExt for Vec<PathBuf> {
-    fn push_if_repo(&mut self, entry: &DirEntry) {
-        if is_repo(entry) {
-            self.push(entry.path().to_path_buf());
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SearchRoot;
+    use std::env;
+    use std::os::unix::fs::symlink;
+
+    const NOW: u64 = 1_700_000_000;
+    const HOUR: u64 = 60 * 60;
+    const DAY: u64 = 24 * HOUR;
+
+    #[test]
+    fn frecency_recency() {
+        assert!(frecency(1, NOW, NOW) > frecency(1, NOW - 2 * HOUR, NOW));
+        assert!(frecency(1, NOW - 2 * HOUR, NOW) > frecency(1, NOW - 2 * DAY, NOW));
+        assert!(frecency(1, NOW - 2 * DAY, NOW) > frecency(1, NOW - 30 * DAY, NOW));
+    }
+
+    #[test]
+    fn frecency_frequency() {
+        assert!(frecency(5, NOW - 2 * HOUR, NOW) > frecency(1, NOW - 2 * HOUR, NOW));
+        // visited often enough an older repo still wins over one picked once just now
+        assert!(frecency(20, NOW - 2 * DAY, NOW) > frecency(1, NOW, NOW));
+        assert_eq!(frecency(0, NOW, NOW), 0.0);
     }
-}
 
-fn is_repo(entry: &DirEntry) -> bool {
-    match Repository::open(entry.path()) {
-        Ok(repo) if repo.workdir().is_some_and(|r| r == entry.path()) => true,
-        _ => false,
+    #[test]
+    fn format_repos_aligned() {
+        let repos = ["~/src/celeris".to_owned(), "/work/a".to_owned()];
+        assert_eq!(
+            format_repos(&Config::default(), &repos),
+            ["celeris -- ~/src/celeris", "a       -- /work/a"]
+        );
+        assert_eq!(format_repos(&Config::default(), &[]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn format_repos_truncated() {
+        let long = "a".repeat(MAX_NAME_WIDTH + 10);
+        let formatted = format_repos(
+            &Config::default(),
+            &[format!("/src/{long}"), "/src/b".to_owned()],
+        );
+        let expected_name = format!("{}…", "a".repeat(MAX_NAME_WIDTH - 1));
+        assert_eq!(formatted[0], format!("{expected_name} -- /src/{long}"));
+        assert_eq!(
+            formatted[1],
+            format!("b{} -- /src/b", " ".repeat(MAX_NAME_WIDTH - 1))
+        );
+    }
+
+    #[test]
+    fn frecency_future_visit() {
+        // e.g. the clock was moved back since
+        assert_eq!(frecency(1, NOW + DAY, NOW), frecency(1, NOW, NOW));
+    }
+
+    #[test]
+    fn unreadable_root() -> Result<()> {
+        let dir = env::temp_dir().join("__celeris_unreadable_root");
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        let readable = dir.join("readable");
+        let repo = readable.join("test1");
+        fs::create_dir_all(&repo)?;
+        Repository::init(&repo)?;
+        let unreadable = dir.join("unreadable");
+        // unlike permissions this can't be read even when running as root
+        symlink(dir.join("missing"), &unreadable)?;
+
+        let root = |path: &Path| SearchRoot {
+            path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let config = Config {
+            search_roots: vec![root(&unreadable), root(&readable)],
+            ..Config::default()
+        };
+        let mut warnings = Vec::new();
+        let mut found = Vec::new();
+        walk(&config, &AtomicBool::new(false), &mut warnings, |repo| {
+            found.push(repo)
+        });
+        assert_eq!(found, [repo]);
+        let warnings = String::from_utf8(warnings)?;
+        assert!(
+            warnings.contains("search root is unreadable, skipping it"),
+            "{warnings}"
+        );
+        Ok(())
     }
 }
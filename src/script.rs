@@ -1,12 +1,20 @@
+mod log;
 mod pane;
+mod record;
 mod session;
 mod window;
 
 use crate::layout::Layout;
-use crate::tmux::{TmuxExecuteExt, tmux};
+use crate::tmux::{TmuxExecuteExt, tmux, with_executor};
 use color_eyre::eyre::{self, Context};
-use mlua::{ExternalResult, Lua, Result};
+pub use log::LayoutLog;
+use mlua::{ExternalResult, Lua, Result, Table};
+use record::Recorder;
+use std::env;
 use std::path::Path;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
 
 fn raw_command(_: &Lua, args: Vec<String>) -> Result<String> {
     let output = tmux()
@@ -19,19 +27,550 @@ fn raw_command(_: &Lua, args: Vec<String>) -> Result<String> {
     Ok(output)
 }
 
-pub fn run(layout: &Layout, layouts_dir: &Path) -> eyre::Result<()> {
-    let lua = Lua::new();
-    lua.set_named_registry_value("CELERIS_SESSION_NAME", layout.tmux_name())?;
+fn sleep(_: &Lua, millis: u64) -> Result<()> {
+    thread::sleep(Duration::from_millis(millis));
+    Ok(())
+}
 
-    let mut api = lua.create_table()?;
-    lua.register_module("celeris", &api)?;
+// Helpers for branching on the machine a layout runs on. Read-only, so they're the same when the
+// layout is only recorded
+fn os(_: &Lua, _: ()) -> Result<&'static str> {
+    Ok(env::consts::OS)
+}
 
-    session::register(&lua, &mut api)?;
-    window::register(&lua, &mut api)?;
-    pane::register(&lua, &mut api)?;
-    api.set("rawCommand", lua.create_function(raw_command)?)?;
+fn hostname(_: &Lua, _: ()) -> Result<String> {
+    hostname::get()
+        .wrap_err("failed to get the hostname")
+        .into_lua_err()?
+        .into_string()
+        .map_err(|hostname| eyre::eyre!("hostname contains invalid unicode: {hostname:?}"))
+        .into_lua_err()
+}
 
-    let layout_path = layout.storage_path(layouts_dir);
-    lua.load(layout_path).exec()?;
-    Ok(())
+fn env_var(_: &Lua, name: String) -> Result<Option<String>> {
+    match env::var(&name) {
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(e)
+            .wrap_err_with(|| format!("failed to read ${name}"))
+            .into_lua_err(),
+    }
+}
+
+fn register_tmux_api(lua: &Lua, api: &mut Table) -> Result<()> {
+    session::register(lua, api)?;
+    window::register(lua, api)?;
+    pane::register(lua, api)?;
+    api.set("rawCommand", lua.create_function(raw_command)?)
+}
+
+// Set when the layout is only recorded, for the parts of the api which don't go through tmux
+fn recorder(lua: &Lua) -> Option<Rc<Recorder>> {
+    lua.app_data_ref::<Rc<Recorder>>()
+        .map(|recorder| Rc::clone(&recorder))
+}
+
+const SESSION_NAME_KEY: &str = "CELERIS_SESSION_NAME";
+// set when `session:attach()` should do nothing
+const NO_ATTACH_KEY: &str = "CELERIS_NO_ATTACH";
+// the client `session:attach()` switches, the current one if unset
+const CLIENT_KEY: &str = "CELERIS_CLIENT";
+
+/// A lua VM with the celeris api registered, which can be reused to run multiple layouts
+pub struct ScriptRuntime {
+    lua: Lua,
+}
+
+impl ScriptRuntime {
+    pub fn new(log: LayoutLog) -> eyre::Result<Self> {
+        Self::with_api(|lua, api| {
+            register_tmux_api(lua, api)?;
+            log::register(lua, api, log)?;
+            api.set("sleep", lua.create_function(sleep)?)
+        })
+    }
+
+    // The tmux commands of the api go to the recorder, as long as it's installed with
+    // `with_executor`. What doesn't go through tmux is noted there by the api
+    fn recording(recorder: Rc<Recorder>) -> eyre::Result<Self> {
+        Self::with_api(|lua, api| {
+            register_tmux_api(lua, api)?;
+            let log_recorder = Rc::clone(&recorder);
+            api.set(
+                "log",
+                lua.create_function(move |_, message: String| {
+                    log_recorder.log(&message);
+                    Ok(())
+                })?,
+            )?;
+            let sleep_recorder = Rc::clone(&recorder);
+            api.set(
+                "sleep",
+                lua.create_function(move |_, millis: u64| {
+                    sleep_recorder.sleep(Duration::from_millis(millis));
+                    Ok(())
+                })?,
+            )?;
+            lua.set_app_data(recorder);
+            Ok(())
+        })
+    }
+
+    fn with_api(register: impl FnOnce(&Lua, &mut Table) -> Result<()>) -> eyre::Result<Self> {
+        let lua = Lua::new();
+        let mut api = lua.create_table()?;
+        lua.register_module("celeris", &api)?;
+        api.set("os", lua.create_function(os)?)?;
+        api.set("hostname", lua.create_function(hostname)?)?;
+        api.set("env", lua.create_function(env_var)?)?;
+        register(&lua, &mut api)?;
+        Ok(Self { lua })
+    }
+
+    /// With attaching turned off `session:attach()` does nothing, so that layouts can create their
+    /// sessions in the background
+    pub fn set_attach(&self, attach: bool) -> eyre::Result<()> {
+        self.lua.set_named_registry_value(NO_ATTACH_KEY, !attach)?;
+        Ok(())
+    }
+
+    /// The client `session:attach()` switches, e.g. its tty, instead of the current one
+    pub fn set_client(&self, client: Option<&str>) -> eyre::Result<()> {
+        self.lua.set_named_registry_value(CLIENT_KEY, client)?;
+        Ok(())
+    }
+
+    /// Runs the layout file, creating the session under `session_name`. Only the per-run values
+    /// are reset, globals set by previous layouts are still visible
+    pub fn run_layout(
+        &self,
+        layout: &Layout,
+        layouts_dir: &Path,
+        session_name: &str,
+    ) -> eyre::Result<()> {
+        let layout_path = layout.storage_path(layouts_dir)?;
+        // the lua error itself carries the chunk name, line and traceback
+        self.exec(&layout_path, session_name)
+            .wrap_err_with(|| format!("failed to run layout file: {layout_path:?}"))
+    }
+
+    fn exec(&self, path: &Path, session_name: &str) -> eyre::Result<()> {
+        self.lua
+            .set_named_registry_value(SESSION_NAME_KEY, session_name)?;
+        self.lua.load(path).exec()?;
+        Ok(())
+    }
+}
+
+pub fn run(
+    layout: &Layout,
+    layouts_dir: &Path,
+    log: LayoutLog,
+    attach: bool,
+    client: Option<&str>,
+) -> eyre::Result<()> {
+    let runtime = ScriptRuntime::new(log)?;
+    runtime.set_attach(attach)?;
+    runtime.set_client(client)?;
+    runtime.run_layout(layout, layouts_dir, layout.tmux_name())
+}
+
+/// Runs the layout with its tmux commands recorded instead of run and returns the steps it would
+/// take
+pub fn validate(layout: &Layout, layouts_dir: &Path) -> eyre::Result<Vec<String>> {
+    Ok(record(|runtime| runtime.run_layout(layout, layouts_dir, layout.tmux_name()))?.steps())
+}
+
+/// Runs the layout with its tmux commands recorded instead of run and returns them as a shell
+/// script. Fractional split sizes are resolved against a pane of 80x24, since the size of the
+/// pane being split isn't known until the script runs
+pub fn print_commands(layout: &Layout, layouts_dir: &Path) -> eyre::Result<String> {
+    Ok(record(|runtime| runtime.run_layout(layout, layouts_dir, layout.tmux_name()))?.script())
+}
+
+fn record(run: impl FnOnce(&ScriptRuntime) -> eyre::Result<()>) -> eyre::Result<Rc<Recorder>> {
+    let recorder = Rc::new(Recorder::new()?);
+    // only what the layout does is recorded, the runtime is dropped once the recorder is gone
+    let _runtime = with_executor(recorder.clone(), || -> eyre::Result<_> {
+        let runtime = ScriptRuntime::recording(Rc::clone(&recorder))?;
+        run(&runtime)?;
+        Ok(runtime)
+    })?;
+    Ok(recorder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tmux::{SessionTarget, Target, TmuxExecutor, with_executor};
+    use std::cell::RefCell;
+    use std::env;
+    use std::fs;
+    use std::iter;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::Command;
+    use std::rc::Rc;
+
+    #[test]
+    fn runtime_reuse() -> eyre::Result<()> {
+        let script = env::temp_dir().join("__celeris_runtime_test.lua");
+        // kept in a global so that it's not collected, which would kill the session
+        fs::write(&script, r#"session = require("celeris").Session.new({})"#)?;
+
+        let runtime = ScriptRuntime::new(LayoutLog::new(
+            env::temp_dir().join("__celeris_runtime_test.log"),
+        ))?;
+        let names = ["__celeris_testing_runtime_1", "__celeris_testing_runtime_2"];
+        names
+            .iter()
+            .try_for_each(|name| runtime.exec(&script, name))?;
+        names.iter().try_for_each(|name| -> eyre::Result<()> {
+            assert!(SessionTarget::new(name).target_exists()?);
+            Ok(())
+        })?;
+
+        names.iter().try_for_each(|name| -> eyre::Result<()> {
+            tmux()?.args(["kill-session", "-t", name]).execute()?;
+            Ok(())
+        })?;
+        fs::remove_file(script)?;
+        Ok(())
+    }
+
+    #[test]
+    fn no_attach() -> eyre::Result<()> {
+        let name = "__celeris_testing_no_attach";
+        let script = env::temp_dir().join(format!("{name}.lua"));
+        fs::write(
+            &script,
+            r#"session = require("celeris").Session.new({}); session:attach()"#,
+        )?;
+
+        let runtime =
+            ScriptRuntime::new(LayoutLog::new(env::temp_dir().join(format!("{name}.log"))))?;
+        runtime.set_attach(false)?;
+        let result = runtime.exec(&script, name);
+        fs::remove_file(script)?;
+        result?;
+        assert!(SessionTarget::new(name).target_exists()?);
+        tmux()?.args(["kill-session", "-t", name]).execute()?;
+        Ok(())
+    }
+
+    #[test]
+    fn host_helpers() -> eyre::Result<()> {
+        let runtime = ScriptRuntime::recording(Rc::new(Recorder::new()?))?;
+        let celeris: Table = runtime.lua.load(r#"require("celeris")"#).eval()?;
+        let os: String = celeris.get::<mlua::Function>("os")?.call(())?;
+        assert_eq!(os, env::consts::OS);
+
+        let hostname: String = celeris.get::<mlua::Function>("hostname")?.call(())?;
+        assert!(!hostname.is_empty());
+
+        let env = celeris.get::<mlua::Function>("env")?;
+        let path: Option<String> = env.call("PATH")?;
+        assert_eq!(path, env::var("PATH").ok());
+        let missing: Option<String> = env.call("__CELERIS_SURELY_NOT_SET")?;
+        assert_eq!(missing, None);
+        Ok(())
+    }
+
+    fn recorded(name: &str, layout: &str) -> eyre::Result<Rc<Recorder>> {
+        let script = env::temp_dir().join(format!("{name}.lua"));
+        fs::write(&script, layout)?;
+        let result = record(|runtime| runtime.exec(&script, name));
+        fs::remove_file(script)?;
+        result
+    }
+
+    #[test]
+    fn validate() -> eyre::Result<()> {
+        let layout = r#"
+            local celeris = require("celeris")
+            local session = celeris.Session.new({ default_window_name = "shell" })
+            local window = celeris.Window.new(session, { name = "editor", root = "backend" })
+            window:default_pane():run_command("nvim")
+            window:default_pane():split("horizontal", { size = "20%" })
+            session:default_window():select()
+            celeris.log("attaching")
+            window:default_pane():wait_for("ready", 1000)
+            celeris.sleep(100)
+            session:attach()
+        "#;
+        let steps = recorded("__celeris_validate_test", layout)?.steps();
+        assert_eq!(steps.len(), 12);
+        assert!(steps[0].starts_with("tmux new-session -d -s __celeris_validate_test"));
+        // the relative root is resolved against the session path read once the commands run
+        assert_eq!(
+            steps[2],
+            "tmux display-message -t ${session_1} -p #{session_path}"
+        );
+        assert!(steps[3].starts_with("tmux new-window -t ${session_1}"));
+        assert!(steps[3].ends_with("-c ${session_path_1}/backend"));
+        assert!(
+            steps[5].starts_with("tmux send-keys -t ${session_1}:${window_2}.${pane_2} -l -- nvim")
+        );
+        assert!(steps[6].contains("-h -l 20%"));
+        assert_eq!(steps[7], "tmux select-window -t ${session_1}:${window_1}");
+        assert_eq!(steps[8], "log: attaching");
+        assert_eq!(
+            steps[9],
+            "wait in pane ${session_1}:${window_2}.${pane_2} for \"ready\" up to 1000ms"
+        );
+        assert_eq!(steps[10], "sleep 100ms");
+        assert_eq!(steps[11], "attach to session ${session_1}");
+        // nothing should've been created
+        assert!(!SessionTarget::new("__celeris_validate_test").target_exists()?);
+
+        let broken = [
+            r#"require("celeris").Session.new({}):does_not_exist()"#,
+            r#"require("celeris").Sesion.new({})"#,
+            r#"
+                local celeris = require("celeris")
+                local window = celeris.Window.new(celeris.Session.new({}), {})
+                window:default_pane():split("horizontal", { size = "0.5.%" })
+            "#,
+            r#"
+                local celeris = require("celeris")
+                local window = celeris.Window.new(celeris.Session.new({}), {})
+                window:default_pane():split("horizontal", { size = "150%" })
+            "#,
+            r#"
+                local celeris = require("celeris")
+                local window = celeris.Window.new(celeris.Session.new({}), {})
+                window:default_pane():split("horizontal", { size = { type = "percentage", value = 0 } })
+            "#,
+            r#"
+                local celeris = require("celeris")
+                local window = celeris.Window.new(celeris.Session.new({}), {})
+                window:default_pane():split("horizontal", { size = "3/2" })
+            "#,
+            r#"
+                local celeris = require("celeris")
+                local window = celeris.Window.new(celeris.Session.new({}), {})
+                window:default_pane():split("horizontal", { size = "1/0" })
+            "#,
+            r#"
+                local celeris = require("celeris")
+                local window = celeris.Window.new(celeris.Session.new({}), {})
+                window:default_pane():split("diagonal", {})
+            "#,
+        ];
+        broken.into_iter().for_each(|layout| {
+            let _ = recorded("__celeris_validate_test_broken", layout)
+                .expect_err("broken layout should fail validation");
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn print_commands() -> eyre::Result<()> {
+        let name = "__celeris_print_test";
+        let layout = r#"
+            local celeris = require("celeris")
+            local session = celeris.Session.new({ root = "/tmp" })
+            local editor = celeris.Window.new(session, { name = "it's an editor" })
+            editor:default_pane():run_command("echo ready")
+            editor:default_pane():split("horizontal", { size = "1/3" })
+            editor:default_pane():wait_for("ready", 5000)
+            celeris.Window.new(session, { name = "logs" }):select()
+        "#;
+        let script = recorded(name, layout)?.script();
+        assert!(script.starts_with("#!/bin/sh\nset -e\n"));
+        // nothing should've been created until the script runs
+        assert!(!SessionTarget::new(name).target_exists()?);
+
+        let script_path = env::temp_dir().join(format!("{name}.sh"));
+        fs::write(&script_path, &script)?;
+        let status = std::process::Command::new("sh")
+            .arg(&script_path)
+            .status()?;
+        fs::remove_file(script_path)?;
+        let windows = tmux()?
+            .args([
+                "list-windows",
+                "-t",
+                name,
+                "-F",
+                "#{window_name} #{window_panes}",
+            ])
+            .execute();
+        tmux()?.args(["kill-session", "-t", name]).execute()?;
+
+        assert!(status.success(), "{script}");
+        // the default window got replaced by the first one
+        assert_eq!(
+            windows?.lines().collect::<Vec<_>>(),
+            ["it's an editor 2", "logs 1"]
+        );
+        Ok(())
+    }
+
+    // Hands out ids the way tmux would, counting the windows and panes created so far, so that the
+    // commands of the api can be compared with the ones of the print-cmd script. Panes are as big
+    // as the recorder assumes, so that fractional sizes resolve the same
+    #[derive(Default)]
+    struct CountingExecutor {
+        commands: RefCell<Vec<Vec<String>>>,
+    }
+
+    impl CountingExecutor {
+        fn record(&self, command: &Command) -> String {
+            let mut args = command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            if matches!(args.first().map(String::as_str), Some("-L" | "-S")) {
+                args.drain(..2);
+            }
+            let mut commands = self.commands.borrow_mut();
+            commands.push(args.clone());
+            let count = |names: &[&str]| {
+                commands
+                    .iter()
+                    .filter(|command| names.contains(&command[0].as_str()))
+                    .count()
+            };
+            let sessions = count(&["new-session"]);
+            let windows = count(&["new-session", "new-window"]);
+            let panes = count(&["new-session", "new-window", "split-window"]);
+            let format = match args[0].as_str() {
+                "display-message" => args.last(),
+                _ => args.iter().skip_while(|arg| *arg != "-F").nth(1),
+            };
+            format.map_or(String::new(), |format| {
+                format
+                    .replace("#{session_id}", &format!("${sessions}"))
+                    .replace("#{window_id}", &format!("@{windows}"))
+                    .replace("#{pane_id}", &format!("%{panes}"))
+                    .replace("#{pane_width}", "80")
+                    .replace("#{pane_height}", "24")
+                    .replace("#{session_path}", "/usr")
+            })
+        }
+    }
+
+    impl TmuxExecutor for CountingExecutor {
+        fn execute(&self, command: &mut Command) -> crate::tmux::Result<String> {
+            Ok(self.record(command))
+        }
+
+        // the session doesn't exist until it's created, everything after that does
+        fn succeeds(&self, command: &mut Command) -> crate::tmux::Result<bool> {
+            let created = self
+                .commands
+                .borrow()
+                .iter()
+                .any(|command| command[0] == "new-session");
+            self.record(command);
+            Ok(created)
+        }
+    }
+
+    // Answers like CountingExecutor, the -L/-S socket arguments dropped. The arguments are logged separated
+    // by the unit separator, one command per line
+    const FAKE_TMUX: &str = r#"#!/bin/sh
+case "$1" in -L|-S) shift 2 ;; esac
+for arg; do printf '%s\037' "$arg"; done >> "$CELERIS_FAKE_TMUX_LOG"
+echo >> "$CELERIS_FAKE_TMUX_LOG"
+count() { grep -c "^\($1\)$(printf '\037')" "$CELERIS_FAKE_TMUX_LOG"; }
+sessions=$(count 'new-session')
+windows=$(count 'new-session\|new-window')
+panes=$(count 'new-session\|new-window\|split-window')
+format=
+previous=
+for arg; do
+    [ "$previous" = -F ] && format=$arg
+    previous=$arg
+done
+[ "$1" = display-message ] && format=$arg
+printf '%s\n' "$format" | sed \
+    -e "s/#{session_id}/\$$sessions/g" \
+    -e "s/#{window_id}/@$windows/g" \
+    -e "s/#{pane_id}/%$panes/g" \
+    -e 's/#{pane_width}/80/g' \
+    -e 's/#{pane_height}/24/g' \
+    -e 's|#{session_path}|/usr|g'
+"#;
+
+    // The script reads the ids tmux hands out into variables instead of running the recorded
+    // commands as they are, this catches it running anything other than what the api would
+    #[test]
+    fn print_commands_match_api() -> eyre::Result<()> {
+        let name = "__celeris_print_api_test";
+        let layout = r#"
+            local celeris = require("celeris")
+            local session = celeris.Session.new({ root = "/usr" })
+            local editor = celeris.Window.new(session, { name = "it's an editor", root = "/" })
+            local pane = editor:default_pane()
+            pane:run_command("nvim; echo done", { keep_alive = true })
+            local right = pane:split("horizontal", { size = "1/3" })
+            right:split("vertical", { size = "20%", root = "/" }):set_title("logs")
+            right:split("vertical", { size = { type = "absolute", value = 5 } })
+            right:respawn("tail -f log")
+            right:set_remain_on_exit(false)
+            right:select()
+            editor:even_out("vertical")
+            celeris.Window.new(session, { position = 3, raw_command = "htop" }):select()
+            celeris.Window.new(session, { position = "before", root = "bin" })
+        "#;
+        let recorded = recorded(name, layout)?;
+        let script_path = env::temp_dir().join(format!("{name}.lua"));
+        fs::write(&script_path, layout)?;
+
+        let executor = Rc::new(CountingExecutor::default());
+        let result = with_executor(executor.clone(), || -> eyre::Result<_> {
+            let runtime =
+                ScriptRuntime::new(LayoutLog::new(env::temp_dir().join(format!("{name}.log"))))?;
+            runtime.exec(&script_path, name)?;
+            let commands = executor.commands.borrow().clone();
+            // the sessions are dropped along with the runtime, which has to go through the
+            // executor too
+            drop(runtime);
+            Ok(commands)
+        });
+        fs::remove_file(&script_path)?;
+        // the script leaves out the checks, see Recorder::script
+        let api = result?
+            .into_iter()
+            .filter(|command| command[0] != "has-session")
+            .collect::<Vec<_>>();
+
+        let bin = env::temp_dir().join(format!("{name}_bin"));
+        fs::create_dir_all(&bin)?;
+        let fake_tmux = bin.join("tmux");
+        fs::write(&fake_tmux, FAKE_TMUX)?;
+        fs::set_permissions(&fake_tmux, fs::Permissions::from_mode(0o755))?;
+        let log = env::temp_dir().join(format!("{name}.commands"));
+        fs::write(&log, "")?;
+        let script = env::temp_dir().join(format!("{name}.sh"));
+        fs::write(&script, recorded.script())?;
+        let path = env::join_paths(
+            iter::once(bin.clone())
+                .chain(env::split_paths(&env::var_os("PATH").unwrap_or_default())),
+        )?;
+        let status = Command::new("sh")
+            .arg(&script)
+            .env("PATH", path)
+            .env("CELERIS_FAKE_TMUX_LOG", &log)
+            .status()?;
+        let printed = fs::read_to_string(&log)?;
+        fs::remove_file(script)?;
+        fs::remove_file(log)?;
+        fs::remove_dir_all(bin)?;
+        assert!(status.success());
+
+        let printed = printed
+            .lines()
+            .map(|line| {
+                line.strip_suffix('\u{1f}')
+                    .unwrap_or(line)
+                    .split('\u{1f}')
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(printed, api);
+        Ok(())
+    }
 }
@@ -0,0 +1,34 @@
+//! Stand-in for the scripting engine when celeris is built without the `lua` feature. Layouts are
+//! still managed as files, but anything that has to run them fails
+
+use crate::layout::Layout;
+use color_eyre::eyre::{self, eyre};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct LayoutLog;
+
+impl LayoutLog {
+    pub fn new(_: PathBuf) -> Self {
+        Self
+    }
+}
+
+fn disabled(layout: &Layout) -> eyre::Error {
+    eyre!(
+        "can't run layout: {}, celeris was built without the lua feature",
+        layout.tmux_name()
+    )
+}
+
+pub fn run(layout: &Layout, _: &Path, _: LayoutLog, _: bool, _: Option<&str>) -> eyre::Result<()> {
+    Err(disabled(layout))
+}
+
+pub fn validate(layout: &Layout, _: &Path) -> eyre::Result<Vec<String>> {
+    Err(disabled(layout))
+}
+
+pub fn print_commands(layout: &Layout, _: &Path) -> eyre::Result<String> {
+    Err(disabled(layout))
+}
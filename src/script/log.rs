@@ -0,0 +1,101 @@
+use crate::script::SESSION_NAME_KEY;
+use color_eyre::eyre::{self, Context};
+use mlua::{ExternalResult, Lua, Result, Table};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// once reached the log is moved aside to <name>.old, so at most twice of this is kept around
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// Log file layouts can write to with `celeris.log`, since anything printed gets clobbered as
+/// soon as tmux attaches
+#[derive(Debug, Clone)]
+pub struct LayoutLog {
+    path: PathBuf,
+}
+
+impl LayoutLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn write(&self, session_name: &str, message: &str) -> eyre::Result<()> {
+        self.rotate()?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .wrap_err("system time is before the unix epoch")?;
+        let mut log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .wrap_err_with(|| format!("failed to open layout log: {:?}", self.path))?;
+        writeln!(
+            log,
+            "[{}.{:03}] [{session_name}] {message}",
+            timestamp.as_secs(),
+            timestamp.subsec_millis()
+        )
+        .wrap_err_with(|| format!("failed to write to layout log: {:?}", self.path))
+    }
+
+    fn rotate(&self) -> eyre::Result<()> {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return Ok(());
+        };
+        if metadata.len() < MAX_LOG_SIZE {
+            return Ok(());
+        }
+
+        fs::rename(&self.path, self.old_path())
+            .wrap_err_with(|| format!("failed to rotate layout log: {:?}", self.path))
+    }
+
+    fn old_path(&self) -> PathBuf {
+        let mut old = self.path.clone().into_os_string();
+        old.push(".old");
+        PathBuf::from(old)
+    }
+}
+
+pub fn register(ctx: &Lua, api: &mut Table, log: LayoutLog) -> Result<()> {
+    let log = ctx.create_function(move |ctx, message: String| {
+        let session_name: String = ctx.named_registry_value(SESSION_NAME_KEY)?;
+        log.write(&session_name, &message).into_lua_err()
+    })?;
+    api.set("log", log)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn write_and_rotate() -> eyre::Result<()> {
+        let log = LayoutLog::new(env::temp_dir().join("__celeris_layout_log_test.log"));
+        let _ = fs::remove_file(&log.path);
+        let _ = fs::remove_file(log.old_path());
+
+        log.write("test", "hello")?;
+        log.write("test", "world")?;
+        let contents = fs::read_to_string(&log.path)?;
+        let lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with('['));
+        assert!(lines[0].ends_with("] [test] hello"));
+        assert!(lines[1].ends_with("] [test] world"));
+
+        fs::write(&log.path, vec![b'x'; MAX_LOG_SIZE as usize])?;
+        log.write("test", "rotated")?;
+        assert_eq!(fs::metadata(log.old_path())?.len(), MAX_LOG_SIZE);
+        let contents = fs::read_to_string(&log.path)?;
+        assert_eq!(contents.lines().count(), 1);
+
+        fs::remove_file(&log.path)?;
+        fs::remove_file(log.old_path())?;
+        Ok(())
+    }
+}
@@ -1,14 +1,20 @@
 use crate::tmux::{self, BuilderTransform, Target};
-use color_eyre::eyre::{self, Context};
 use mlua::{ExternalResult, FromLua, Lua, LuaSerdeExt, Result, Table, UserData};
 use serde::{Deserialize, Serialize};
+use std::error;
+use std::fmt::Display;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Clone, Serialize, Deserialize)]
+// The tmux flags and the sides panes end up on are accepted too, only the axis of the split is
+// taken from the latter, the new pane always goes right or down
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Direction {
+    #[serde(alias = "h", alias = "left", alias = "right")]
     Horizontal,
+    #[serde(alias = "v", alias = "up", alias = "down")]
     Vertical,
 }
 
@@ -29,18 +35,13 @@ impl FromLua for Direction {
     }
 }
 
-/*#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
 enum SplitSize {
     Absolute { value: u32 },
     Percentage { value: u8 },
-}
-
-impl FromLua for SplitSize {
-    fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
-        lua.from_value(value)
-    }
+    Fraction { numerator: u32, denominator: u32 },
 }
 
 impl From<SplitSize> for tmux::SplitSize {
@@ -48,45 +49,59 @@ impl From<SplitSize> for tmux::SplitSize {
         match value {
             SplitSize::Absolute { value } => tmux::SplitSize::Absolute(value),
             SplitSize::Percentage { value } => tmux::SplitSize::Percentage(value),
+            SplitSize::Fraction {
+                numerator,
+                denominator,
+            } => tmux::SplitSize::Fraction {
+                numerator,
+                denominator,
+            },
+        }
+    }
+}
+
+// The string form is kept around so that existing layouts don't break
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum SizeOption {
+    Structured(SplitSize),
+    Text(String),
+}
+
+impl SizeOption {
+    fn try_into_split_size(self) -> std::result::Result<tmux::SplitSize, SizeError> {
+        match self {
+            Self::Structured(size) => Ok(size.into()),
+            Self::Text(size) => parse_split_size(&size),
         }
     }
 }
-*/
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SplitOptions {
-    root: Option<PathBuf>,
-    size: Option<String>,
+    pub(super) root: Option<PathBuf>,
+    size: Option<SizeOption>,
 }
 
 impl SplitOptions {
+    fn split_size(&self) -> std::result::Result<Option<tmux::SplitSize>, SizeError> {
+        self.size
+            .clone()
+            .map(|size| {
+                let size = size.try_into_split_size()?;
+                size.validate()
+                    .map_err(|e| SizeError::OutOfRange(e.to_string()))?;
+                Ok(size)
+            })
+            .transpose()
+    }
+
     fn try_into_builder(
         self,
         sibling_pane: Arc<tmux::Pane>,
         direction: Direction,
     ) -> Result<tmux::SplitBuilder> {
-        let size = self
-            .size
-            .map(|s| -> eyre::Result<tmux::SplitSize> {
-                let size = s.trim();
-                if size.ends_with("%") {
-                    Ok(tmux::SplitSize::Percentage(
-                        size.strip_suffix("%")
-                            .expect(
-                                "split size which ends with % should be strippable from the % sign",
-                            )
-                            .parse::<u8>()
-                            .wrap_err_with(|| format!("failed to parse percentage size: {size}"))?,
-                    ))
-                } else {
-                    Ok(tmux::SplitSize::Absolute(
-                        size.parse::<u32>()
-                            .wrap_err_with(|| format!("failed to parse percentage size: {size}"))?,
-                    ))
-                }
-            })
-            .transpose()
-            .into_lua_err()?;
+        let size = self.split_size().into_lua_err()?;
 
         Ok(sibling_pane
             .split(direction.into())
@@ -96,6 +111,96 @@ impl SplitOptions {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum SizeError {
+    Empty,
+    Whitespace(String),
+    Negative(String),
+    InvalidAbsolute(String),
+    InvalidPercentage(String),
+    InvalidFraction(String),
+    /// Parsed, but degenerate, e.g. 0% or 3/2
+    OutOfRange(String),
+}
+
+impl Display for SizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "split size is empty"),
+            Self::Whitespace(size) => write!(f, "split size contains whitespace: {size:?}"),
+            Self::Negative(size) => write!(f, "split size can't be negative: {size}"),
+            Self::InvalidAbsolute(size) => write!(f, "failed to parse absolute size: {size}"),
+            Self::InvalidPercentage(size) => write!(f, "failed to parse percentage size: {size}"),
+            Self::InvalidFraction(size) => write!(f, "failed to parse fraction size: {size}"),
+            Self::OutOfRange(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl error::Error for SizeError {}
+
+// "20" is absolute, "20%" a percentage, "33.3%" and "1/3" get turned into a fraction
+fn parse_split_size(size: &str) -> std::result::Result<tmux::SplitSize, SizeError> {
+    let size = size.trim();
+    if size.is_empty() {
+        return Err(SizeError::Empty);
+    }
+    if size.contains(char::is_whitespace) {
+        return Err(SizeError::Whitespace(size.to_owned()));
+    }
+    if size.starts_with("-") {
+        return Err(SizeError::Negative(size.to_owned()));
+    }
+
+    if let Some(percentage) = size.strip_suffix("%") {
+        let invalid = || SizeError::InvalidPercentage(size.to_owned());
+        if is_number(percentage) {
+            return Ok(tmux::SplitSize::Percentage(
+                percentage.parse().map_err(|_| invalid())?,
+            ));
+        }
+        return parse_fractional_percentage(percentage).ok_or_else(invalid);
+    }
+
+    if let Some((numerator, denominator)) = size.split_once("/") {
+        let parse = |part: &str| {
+            part.parse::<u32>()
+                .ok()
+                .filter(|_| is_number(part))
+                .ok_or_else(|| SizeError::InvalidFraction(size.to_owned()))
+        };
+        return Ok(tmux::SplitSize::Fraction {
+            numerator: parse(numerator)?,
+            denominator: parse(denominator)?,
+        });
+    }
+
+    size.parse()
+        .ok()
+        .filter(|_| is_number(size))
+        .map(tmux::SplitSize::Absolute)
+        .ok_or_else(|| SizeError::InvalidAbsolute(size.to_owned()))
+}
+
+// str::parse lets a leading + through
+fn is_number(part: &str) -> bool {
+    !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())
+}
+
+fn parse_fractional_percentage(percentage: &str) -> Option<tmux::SplitSize> {
+    let (whole, decimal) = percentage.split_once(".")?;
+    if !is_number(whole) || !is_number(decimal) {
+        return None;
+    }
+
+    let scale = 10u32.checked_pow(u32::try_from(decimal.len()).ok()?)?;
+    let numerator = format!("{whole}{decimal}").parse::<u32>().ok()?;
+    Some(tmux::SplitSize::Fraction {
+        numerator,
+        denominator: scale.checked_mul(100)?,
+    })
+}
+
 impl UserData for SplitOptions {}
 
 impl FromLua for SplitOptions {
@@ -104,6 +209,19 @@ impl FromLua for SplitOptions {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RunOptions {
+    pub(super) keep_alive: Option<bool>,
+}
+
+impl UserData for RunOptions {}
+
+impl FromLua for RunOptions {
+    fn from_lua(value: mlua::Value, lua: &Lua) -> Result<Self> {
+        lua.from_value(value)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Pane {
     inner: Arc<tmux::Pane>,
@@ -127,8 +245,42 @@ impl Pane {
         Ok(())
     }
 
-    fn run_command(_: &Lua, this: &Self, command: String) -> Result<()> {
-        this.inner.run_command(&command).into_lua_err()?;
+    fn run_command(
+        _: &Lua,
+        this: &Self,
+        (command, opts): (String, Option<RunOptions>),
+    ) -> Result<()> {
+        match opts.unwrap_or_default().keep_alive.unwrap_or(false) {
+            true => this.inner.run_command_keep_alive(&command),
+            false => this.inner.run_command(&command),
+        }
+        .into_lua_err()?;
+        Ok(())
+    }
+
+    fn set_title(_: &Lua, this: &Self, title: String) -> Result<()> {
+        this.inner.set_title(&title).into_lua_err()?;
+        Ok(())
+    }
+
+    fn set_remain_on_exit(_: &Lua, this: &Self, remain: bool) -> Result<()> {
+        this.inner.set_remain_on_exit(remain).into_lua_err()?;
+        Ok(())
+    }
+
+    fn respawn(_: &Lua, this: &Self, command: Option<String>) -> Result<()> {
+        this.inner.respawn(command.as_deref()).into_lua_err()?;
+        Ok(())
+    }
+
+    fn wait_for(lua: &Lua, this: &Self, (pattern, timeout_ms): (String, u64)) -> Result<()> {
+        let timeout = Duration::from_millis(timeout_ms);
+        // there's nothing to wait for when the commands aren't run
+        if let Some(recorder) = super::recorder(lua) {
+            recorder.wait_for(this.inner.target().get(), &pattern, timeout);
+            return Ok(());
+        }
+        this.inner.wait_for(&pattern, timeout).into_lua_err()?;
         Ok(())
     }
 
@@ -142,6 +294,10 @@ impl UserData for Pane {
         methods.add_method("split", Pane::split);
         methods.add_method("select", Pane::select);
         methods.add_method("run_command", Pane::run_command);
+        methods.add_method("set_title", Pane::set_title);
+        methods.add_method("set_remain_on_exit", Pane::set_remain_on_exit);
+        methods.add_method("respawn", Pane::respawn);
+        methods.add_method("wait_for", Pane::wait_for);
         methods.add_method("target", Pane::target);
     }
 }
@@ -161,7 +317,9 @@ mod tests {
     use serde::Serialize;
 
     use crate::script::pane::Direction;
+    use crate::script::pane::RunOptions;
     use crate::script::pane::SplitOptions;
+    use crate::script::pane::{SizeError, parse_split_size};
     use crate::tmux::SessionBuilder as TmuxSessionBuilder;
     use crate::tmux::SplitSize as TmuxSplitSize;
     use crate::tmux::WindowBuilder as TmuxWindowBuilder;
@@ -175,6 +333,32 @@ mod tests {
         direction: Direction,
     }
 
+    #[test]
+    fn direction_aliases() -> Result<()> {
+        let lua = Lua::new();
+        let parse = |direction: &str| {
+            lua.from_value::<Direction>(mlua::Value::String(lua.create_string(direction)?))
+        };
+        for direction in ["horizontal", "h", "left", "right"] {
+            assert!(matches!(parse(direction)?, Direction::Horizontal));
+        }
+        for direction in ["vertical", "v", "up", "down"] {
+            assert!(matches!(parse(direction)?, Direction::Vertical));
+        }
+        assert!(parse("diagonal").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn run_options() -> Result<()> {
+        let lua = Lua::new();
+        let opts: RunOptions = lua.from_value(lua.load("{ keep_alive = true }").eval()?)?;
+        assert_eq!(opts.keep_alive, Some(true));
+        let opts: RunOptions = lua.from_value(lua.load("{}").eval()?)?;
+        assert_eq!(opts.keep_alive, None);
+        Ok(())
+    }
+
     #[test]
     fn split_options() -> Result<()> {
         let lua = Lua::new();
@@ -198,6 +382,11 @@ mod tests {
             r#"{ size = "{{absolute_size}}" }"#,
             r#"{ size = "{{percentage_size}}%" }"#,
             r#"{ root = "{{root}}" }"#,
+            r#"{ size = "1/3" }"#,
+            r#"{ size = "33.3%" }"#,
+            r#"{ size = { type = "absolute", value = {{absolute_size}} } }"#,
+            r#"{ size = { type = "percentage", value = {{percentage_size}} } }"#,
+            r#"{ size = { type = "fraction", numerator = 1, denominator = 3 } }"#,
         ]
         .into_iter()
         .map(|opt| handlebars.render_template(opt, &opt_data).into_lua_err())
@@ -206,30 +395,54 @@ mod tests {
 
         let builders_got = opts_given
             .into_iter()
-            .map(|opt| opt.try_into_builder(Arc::clone(&default_pane), opt_data.direction.clone()))
+            .map(|opt| opt.try_into_builder(Arc::clone(&default_pane), opt_data.direction))
             .collect::<Result<Vec<_>>>()?;
 
         let buliders_expected: Vec<_> = vec![
             default_pane
-                .split(opt_data.direction.clone().into())
-                .size(TmuxSplitSize::Absolute(opt_data.absolute_size.clone()))
+                .split(opt_data.direction.into())
+                .size(TmuxSplitSize::Absolute(opt_data.absolute_size))
                 .root(opt_data.root.clone())
                 .into_lua_err()?,
             default_pane
-                .split(opt_data.direction.clone().into())
-                .size(TmuxSplitSize::Percentage(opt_data.percentage_size.clone()))
+                .split(opt_data.direction.into())
+                .size(TmuxSplitSize::Percentage(opt_data.percentage_size))
                 .root(opt_data.root.clone())
                 .into_lua_err()?,
             default_pane
-                .split(opt_data.direction.clone().into())
-                .size(TmuxSplitSize::Absolute(opt_data.absolute_size.clone())),
+                .split(opt_data.direction.into())
+                .size(TmuxSplitSize::Absolute(opt_data.absolute_size)),
             default_pane
-                .split(opt_data.direction.clone().into())
-                .size(TmuxSplitSize::Percentage(opt_data.percentage_size.clone())),
+                .split(opt_data.direction.into())
+                .size(TmuxSplitSize::Percentage(opt_data.percentage_size)),
             default_pane
-                .split(opt_data.direction.clone().into())
+                .split(opt_data.direction.into())
                 .root(opt_data.root.clone())
                 .into_lua_err()?,
+            default_pane
+                .split(opt_data.direction.into())
+                .size(TmuxSplitSize::Fraction {
+                    numerator: 1,
+                    denominator: 3,
+                }),
+            default_pane
+                .split(opt_data.direction.into())
+                .size(TmuxSplitSize::Fraction {
+                    numerator: 333,
+                    denominator: 1000,
+                }),
+            default_pane
+                .split(opt_data.direction.into())
+                .size(TmuxSplitSize::Absolute(opt_data.absolute_size)),
+            default_pane
+                .split(opt_data.direction.into())
+                .size(TmuxSplitSize::Percentage(opt_data.percentage_size)),
+            default_pane
+                .split(opt_data.direction.into())
+                .size(TmuxSplitSize::Fraction {
+                    numerator: 1,
+                    denominator: 3,
+                }),
         ];
 
         assert_eq!(buliders_expected, builders_got);
@@ -238,6 +451,9 @@ mod tests {
             r#"{ size = "&{{absolute_size}}" }"#,
             r#"{ size = " {{percentage_size}} %" }"#,
             r#"{ size = "-{{percentage_size}}-% " }"#,
+            r#"{ size = "1/" }"#,
+            r#"{ size = ".5%" }"#,
+            r#"{ size = "1.-5%" }"#,
         ]
         .into_iter()
         .map(|opt| handlebars.render_template(opt, &opt_data).into_lua_err())
@@ -246,10 +462,40 @@ mod tests {
 
         opts_given
             .into_iter()
-            .map(|opt| opt.try_into_builder(Arc::clone(&default_pane), opt_data.direction.clone()))
+            .map(|opt| opt.try_into_builder(Arc::clone(&default_pane), opt_data.direction))
             .for_each(|result| {
                 let _ = result.expect_err("should fail under eroneous value");
             });
+
+        [
+            r#"{ size = { type = "percentage", value = -5 } }"#,
+            r#"{ size = { type = "ratio", value = 5 } }"#,
+            r#"{ size = { value = 5 } }"#,
+        ]
+        .into_iter()
+        .for_each(|opt| {
+            let _ = lua
+                .load(opt)
+                .eval()
+                .and_then(|opt| lua.from_value::<SplitOptions>(opt))
+                .expect_err("malformed structured size should fail to deserialize");
+        });
+
+        let errors_expected = [
+            ("", SizeError::Empty),
+            ("&420", SizeError::InvalidAbsolute("&420".to_owned())),
+            ("+420", SizeError::InvalidAbsolute("+420".to_owned())),
+            (" 69 %", SizeError::Whitespace("69 %".to_owned())),
+            ("-69-% ", SizeError::Negative("-69-%".to_owned())),
+            ("-420", SizeError::Negative("-420".to_owned())),
+            ("420%", SizeError::InvalidPercentage("420%".to_owned())),
+            (".5%", SizeError::InvalidPercentage(".5%".to_owned())),
+            ("1/", SizeError::InvalidFraction("1/".to_owned())),
+            ("1/+3", SizeError::InvalidFraction("1/+3".to_owned())),
+        ];
+        errors_expected.into_iter().for_each(|(size, error)| {
+            assert_eq!(parse_split_size(size), Err(error), "size: {size:?}");
+        });
         Ok(())
     }
 }
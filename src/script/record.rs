@@ -0,0 +1,348 @@
+// Runs layouts against the real api without touching tmux. The commands are recorded instead of
+// being run and the ids tmux would hand out are made up, as shell variables which the printed
+// script reads from the output of the commands creating them. So is the session path relative
+// roots are resolved against. What doesn't go through tmux, e.g.
+// sleeping or attaching, is noted by the api itself
+use crate::tmux::{self, TmuxExecutor};
+use itertools::Itertools;
+use std::cell::RefCell;
+use std::iter;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+// the size of the panes that are split by a fraction isn't known until the script runs
+const PANE_WIDTH: &str = "80";
+const PANE_HEIGHT: &str = "24";
+// what the api reads from tmux, other than the variables
+const VALUES: [(&str, &str); 3] = [
+    ("#{pane_width}", PANE_WIDTH),
+    ("#{pane_height}", PANE_HEIGHT),
+    ("#{session_windows}", "1"),
+];
+// only known once the script runs, so they're read into variables
+const VARIABLES: [(&str, &str); 4] = [
+    ("#{session_id}", "session"),
+    ("#{window_id}", "window"),
+    ("#{pane_id}", "pane"),
+    ("#{session_path}", "session_path"),
+];
+// the api delimits the values it asks for with it
+const OUTPUT_DELIM: char = '|';
+
+#[derive(Debug)]
+enum Step {
+    Command {
+        args: Vec<String>,
+        // the variables the `|` delimited output is read into, empty if there is no output
+        outputs: Vec<Option<String>>,
+    },
+    Sleep(Duration),
+    WaitFor {
+        target: String,
+        pattern: String,
+        timeout: Duration,
+    },
+    Attach(String),
+    Log(String),
+}
+
+#[derive(Debug)]
+pub struct Recorder {
+    steps: RefCell<Vec<Step>>,
+    // the variables handed out so far
+    vars: RefCell<Vec<String>>,
+    // the tmux invocation along with the socket celeris was told to use
+    tmux: Vec<String>,
+}
+
+impl Recorder {
+    pub fn new() -> tmux::Result<Self> {
+        let tmux = tmux::tmux()?;
+        let tmux = iter::once(tmux.get_program())
+            .chain(tmux.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        Ok(Self {
+            steps: RefCell::default(),
+            vars: RefCell::default(),
+            tmux,
+        })
+    }
+
+    pub fn sleep(&self, duration: Duration) {
+        self.steps.borrow_mut().push(Step::Sleep(duration));
+    }
+
+    pub fn wait_for(&self, target: &str, pattern: &str, timeout: Duration) {
+        self.steps.borrow_mut().push(Step::WaitFor {
+            target: target.to_owned(),
+            pattern: pattern.to_owned(),
+            timeout,
+        });
+    }
+
+    pub fn attach(&self, target: &str) {
+        self.steps
+            .borrow_mut()
+            .push(Step::Attach(target.to_owned()));
+    }
+
+    pub fn log(&self, message: &str) {
+        self.steps.borrow_mut().push(Step::Log(message.to_owned()));
+    }
+
+    /// The steps the layout would take, one per line
+    pub fn steps(&self) -> Vec<String> {
+        self.steps
+            .borrow()
+            .iter()
+            .map(|step| match step {
+                Step::Command { args, .. } => format!("tmux {}", args.join(" ")),
+                Step::Sleep(duration) => format!("sleep {}ms", duration.as_millis()),
+                Step::WaitFor {
+                    target,
+                    pattern,
+                    timeout,
+                } => format!(
+                    "wait in pane {target} for {pattern:?} up to {}ms",
+                    timeout.as_millis()
+                ),
+                Step::Attach(target) => format!("attach to session {target}"),
+                Step::Log(message) => format!("log: {message}"),
+            })
+            .collect()
+    }
+
+    /// The tmux commands as a shell script, which stops at the first one that fails. The checks
+    /// celeris does before each command, e.g. whether the target exists, are left out
+    pub fn script(&self) -> String {
+        let lines = self
+            .steps
+            .borrow()
+            .iter()
+            .map(|step| self.script_step(step))
+            .collect_vec();
+        iter::once("#!/bin/sh".to_owned())
+            .chain(iter::once("set -e".to_owned()))
+            .chain(lines)
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    fn script_step(&self, step: &Step) -> String {
+        match step {
+            Step::Command { args, outputs } => {
+                let command = self.command(args);
+                match &outputs[..] {
+                    [] => command,
+                    // read by celeris, but not needed once the script is put together
+                    outputs if outputs.iter().all(Option::is_none) => {
+                        format!("{command} >/dev/null")
+                    }
+                    [Some(var)] => format!("{var}=$({command})"),
+                    outputs => {
+                        let vars = outputs
+                            .iter()
+                            .map(|var| var.as_deref().unwrap_or("_"))
+                            .join(" ");
+                        format!(
+                            "ids=$({command})\nIFS='{OUTPUT_DELIM}' read -r {vars} <<EOF\n$ids\nEOF"
+                        )
+                    }
+                }
+            }
+            Step::Sleep(duration) => format!(
+                "sleep {}.{:03}",
+                duration.as_secs(),
+                duration.subsec_millis()
+            ),
+            // polled every 100ms, like Pane::wait_for
+            Step::WaitFor {
+                target,
+                pattern,
+                timeout,
+            } => {
+                let tries = timeout.as_millis().div_ceil(100).max(1);
+                let capture = self.command(&["capture-pane", "-p", "-t", target].map(String::from));
+                let timed_out = quote(&format!(
+                    "timed out after {}ms waiting for: {pattern:?}",
+                    timeout.as_millis()
+                ));
+                format!(
+                    "tries=0\n\
+                    until {capture} | grep -qF -- {}; do\n    \
+                        tries=$((tries + 1))\n    \
+                        [ \"$tries\" -lt {tries} ] || {{ echo {timed_out} >&2; exit 1; }}\n    \
+                        sleep 0.1\n\
+                    done",
+                    quote(pattern)
+                )
+            }
+            Step::Attach(target) => {
+                let switch = self.command(&["switch-client", "-t", target].map(String::from));
+                let attach = self.command(&["attach-session", "-t", target].map(String::from));
+                format!("if [ -n \"$TMUX\" ]; then\n    {switch}\nelse\n    {attach}\nfi")
+            }
+            Step::Log(message) => format!("# log: {}", message.replace('\n', " ")),
+        }
+    }
+
+    fn command(&self, args: &[String]) -> String {
+        self.tmux
+            .iter()
+            .map(|arg| quote(arg))
+            .chain(args.iter().map(|arg| self.quote_with_vars(arg)))
+            .join(" ")
+    }
+
+    // The variables are expanded by the shell, everything else is taken literally
+    fn quote_with_vars(&self, arg: &str) -> String {
+        let vars = self.vars.borrow();
+        let placeholders = vars.iter().map(|var| placeholder(var)).collect_vec();
+        if !placeholders
+            .iter()
+            .any(|placeholder| arg.contains(placeholder))
+        {
+            return quote(arg);
+        }
+
+        let mut quoted = String::from("\"");
+        let mut rest = arg;
+        while !rest.is_empty() {
+            let next = placeholders
+                .iter()
+                .filter_map(|placeholder| Some((rest.find(placeholder)?, placeholder)))
+                .min_by_key(|(position, _)| *position);
+            let Some((position, placeholder)) = next else {
+                quoted.push_str(&escape_double_quoted(rest));
+                break;
+            };
+            quoted.push_str(&escape_double_quoted(&rest[..position]));
+            quoted.push_str(placeholder);
+            rest = &rest[position + placeholder.len()..];
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    fn args(&self, command: &Command) -> Vec<String> {
+        // the socket arguments are already part of the recorded invocation
+        command
+            .get_args()
+            .skip(self.tmux.len() - 1)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    // The format the output is requested in, if there is one
+    fn format(args: &[String]) -> Option<&String> {
+        match args.first().map(String::as_str) {
+            Some("display-message") if args.iter().any(|arg| arg == "-p") => args.last(),
+            _ => args.iter().skip_while(|arg| *arg != "-F").nth(1),
+        }
+    }
+
+    fn new_var(&self, kind: &str) -> String {
+        let mut vars = self.vars.borrow_mut();
+        let count = vars
+            .iter()
+            .filter(|var| {
+                var.rsplit_once('_')
+                    .is_some_and(|(prefix, _)| prefix == kind)
+            })
+            .count();
+        let var = format!("{kind}_{}", count + 1);
+        vars.push(var.clone());
+        var
+    }
+
+    // Answers with the format filled in, what's only known once the script runs is a fresh variable
+    fn output(&self, format: &str) -> (String, Vec<Option<String>>) {
+        let mut outputs = Vec::new();
+        let output = format
+            .split(OUTPUT_DELIM)
+            .map(|part| {
+                let variable = VARIABLES.iter().find(|(format, _)| *format == part);
+                match variable {
+                    Some((_, kind)) => {
+                        let var = self.new_var(kind);
+                        outputs.push(Some(var.clone()));
+                        placeholder(&var)
+                    }
+                    None => {
+                        outputs.push(None);
+                        VALUES
+                            .iter()
+                            .fold(part.to_owned(), |part, (format, value)| {
+                                part.replace(format, value)
+                            })
+                    }
+                }
+            })
+            .join(&OUTPUT_DELIM.to_string());
+        (output, outputs)
+    }
+}
+
+impl TmuxExecutor for Recorder {
+    fn execute(&self, command: &mut Command) -> tmux::Result<String> {
+        let args = self.args(command);
+        let (output, outputs) = match Self::format(&args) {
+            Some(format) => {
+                let (output, outputs) = self.output(format);
+                (output + "\n", outputs)
+            }
+            None => (String::new(), Vec::new()),
+        };
+        self.steps
+            .borrow_mut()
+            .push(Step::Command { args, outputs });
+        Ok(output)
+    }
+
+    // Only the targets which were created exist, so the session of the layout is new. The checks
+    // aren't recorded, see `script`
+    fn succeeds(&self, command: &mut Command) -> tmux::Result<bool> {
+        let args = self.args(command);
+        Ok(self
+            .vars
+            .borrow()
+            .iter()
+            .any(|var| args.iter().any(|arg| arg.contains(&placeholder(var)))))
+    }
+
+    fn known_when_run(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy();
+        self.vars
+            .borrow()
+            .iter()
+            .any(|var| path.contains(&placeholder(var)))
+    }
+}
+
+// How a variable shows up in the targets the api puts together
+fn placeholder(var: &str) -> String {
+    format!("${{{var}}}")
+}
+
+// Single quotes unless the argument is only made of characters the shell doesn't treat specially
+fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c));
+    match plain {
+        true => arg.to_owned(),
+        false => format!("'{}'", arg.replace('\'', r"'\''")),
+    }
+}
+
+fn escape_double_quoted(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '"' | '\\' | '$' | '`' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
@@ -1,3 +1,4 @@
+use crate::script::window::Window;
 use crate::tmux::BuilderTransform;
 use crate::tmux::{self, Target};
 use color_eyre::eyre::WrapErr;
@@ -8,15 +9,22 @@ use serde::{Deserialize, Serialize};
 use std::{path::PathBuf, sync::Arc};
 
 #[derive(Deserialize, Serialize, Debug)]
-struct SessionOptions {
-    root: Option<PathBuf>,
+pub struct SessionOptions {
+    pub(super) root: Option<PathBuf>,
+    pub(super) default_window_name: Option<String>,
+    pub(super) group: Option<String>,
 }
 
 impl SessionOptions {
     fn try_into_builder(self, session_name: String) -> Result<tmux::SessionBuilder> {
         Ok(tmux::SessionBuilder::new(session_name)
             .try_builder_transform(self.root, tmux::SessionBuilder::root)
-            .into_lua_err()?)
+            .into_lua_err()?
+            .builder_transform(
+                self.default_window_name,
+                tmux::SessionBuilder::default_window_name,
+            )
+            .builder_transform(self.group, tmux::SessionBuilder::group))
     }
 }
 
@@ -37,7 +45,7 @@ pub struct Session {
 impl Session {
     fn try_new(ctx: &Lua, opts: SessionOptions) -> Result<Session> {
         let session_name: String = ctx
-            .named_registry_value("CELERIS_SESSION_NAME")
+            .named_registry_value(super::SESSION_NAME_KEY)
             .wrap_err("failed to get session name from the lua registry")
             .into_lua_err()?;
 
@@ -53,14 +61,31 @@ impl Session {
         self.inner
     }
 
-    fn attach(_: &Lua, this: &mut Self, _: ()) -> Result<()> {
-        this.inner.attach().into_lua_err()?;
+    fn attach(lua: &Lua, this: &mut Self, _: ()) -> Result<()> {
+        let no_attach: Option<bool> = lua.named_registry_value(super::NO_ATTACH_KEY)?;
+        if no_attach.unwrap_or(false) {
+            return Ok(());
+        }
+        // attaching hands over the terminal instead of going through the recorder
+        if let Some(recorder) = super::recorder(lua) {
+            recorder.attach(this.inner.target().get());
+            return Ok(());
+        }
+        let client: Option<String> = lua.named_registry_value(super::CLIENT_KEY)?;
+        this.inner.attach_with(client.as_deref()).into_lua_err()?;
         Ok(())
     }
 
     fn target(_: &Lua, this: &Self, _: ()) -> Result<String> {
         Ok(this.inner.target().get().to_owned())
     }
+
+    fn default_window(_: &Lua, this: &Self, _: ()) -> Result<Option<Window>> {
+        Ok(this
+            .inner
+            .default_window()
+            .map(|window| Window::new(Arc::new(window.clone()))))
+    }
 }
 
 impl UserData for Session {
@@ -68,6 +93,7 @@ impl UserData for Session {
         methods.add_function("new", Session::try_new);
         methods.add_method_mut("attach", Session::attach);
         methods.add_method("target", Session::target);
+        methods.add_method("default_window", Session::default_window);
     }
 }
 
@@ -98,11 +124,16 @@ mod tests {
         let opt_data = OptData {
             root: env::temp_dir(),
         };
-        let given_opts: Vec<_> = ["{ root = \"{{root}}\" }", "{}"]
-            .into_iter()
-            .map(|opt| handlebars.render_template(opt, &opt_data).into_lua_err())
-            .map(|opt| lua.from_value::<SessionOptions>(lua.load(opt?).eval()?))
-            .collect::<Result<Vec<_>>>()?;
+        let given_opts: Vec<_> = [
+            "{ root = \"{{root}}\" }",
+            "{}",
+            "{ default_window_name = \"first\" }",
+            "{ group = \"base\" }",
+        ]
+        .into_iter()
+        .map(|opt| handlebars.render_template(opt, &opt_data).into_lua_err())
+        .map(|opt| lua.from_value::<SessionOptions>(lua.load(opt?).eval()?))
+        .collect::<Result<Vec<_>>>()?;
 
         let got_builders = given_opts
             .into_iter()
@@ -114,6 +145,8 @@ mod tests {
                 .root(opt_data.root)
                 .into_lua_err()?,
             tmux::SessionBuilder::new("test".to_owned()),
+            tmux::SessionBuilder::new("test".to_owned()).default_window_name("first".to_owned()),
+            tmux::SessionBuilder::new("test".to_owned()).group("base".to_owned()),
         ];
 
         assert_eq!(expected_builders, got_builders);
@@ -13,9 +13,29 @@ use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WindowOptions {
-    name: Option<String>,
-    root: Option<PathBuf>,
-    raw_command: Option<String>,
+    pub(super) name: Option<String>,
+    pub(super) root: Option<PathBuf>,
+    pub(super) raw_command: Option<String>,
+    pub(super) position: Option<Position>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum Position {
+    Before,
+    After,
+    #[serde(untagged)]
+    Index(u32),
+}
+
+impl Position {
+    fn apply(self, builder: tmux::WindowBuilder) -> tmux::WindowBuilder {
+        match self {
+            Self::Before => builder.before(),
+            Self::After => builder.after(),
+            Self::Index(index) => builder.index(index),
+        }
+    }
 }
 
 impl WindowOptions {
@@ -24,7 +44,8 @@ impl WindowOptions {
             .builder_transform(self.name, tmux::WindowBuilder::name)
             .try_builder_transform(self.root, tmux::WindowBuilder::root)
             .into_lua_err()?
-            .builder_transform(self.raw_command, tmux::WindowBuilder::raw_command))
+            .builder_transform(self.raw_command, tmux::WindowBuilder::raw_command)
+            .builder_transform(self.position, |builder, position| position.apply(builder)))
     }
 }
 
@@ -42,6 +63,10 @@ pub struct Window {
 }
 
 impl Window {
+    pub fn new(inner: Arc<tmux::Window>) -> Window {
+        Self { inner }
+    }
+
     fn try_new(_: &Lua, (session, opts): (Session, WindowOptions)) -> Result<Window> {
         let builder = opts.try_into_builder(session.inner())?;
         Ok(Self {
@@ -124,6 +149,9 @@ mod tests {
             "{ name = \"{{name}}\" }",
             "{ root = \"{{root}}\" }",
             "{ raw_command = \"{{raw_command}}\" }",
+            "{ position = \"before\" }",
+            "{ position = \"after\" }",
+            "{ position = 3 }",
         ]
         .into_iter()
         .map(|opt| handlebars.render_template(opt, &opt_data).into_lua_err())
@@ -153,6 +181,9 @@ mod tests {
                 .root(opt_data.root.clone())
                 .into_lua_err()?,
             TmuxWindowBuilder::new(Arc::clone(&session)).raw_command(opt_data.raw_command.clone()),
+            TmuxWindowBuilder::new(Arc::clone(&session)).before(),
+            TmuxWindowBuilder::new(Arc::clone(&session)).after(),
+            TmuxWindowBuilder::new(Arc::clone(&session)).index(3),
         ];
 
         assert_eq!(buliders_expected, builders_got);
@@ -1,58 +1,213 @@
 use crate::config::Config;
 use crate::directory_manager::DirectoryManager;
-use crate::layout::CreateLayoutOptions;
-use crate::layout::Layout;
-use crate::layout::LayoutManager;
-use crate::layout::LayoutName;
+use crate::layout::{self, CreateLayoutOptions, Layout, LayoutManager, LayoutName};
+use crate::repo_search;
 use crate::script;
-use crate::tmux::Session;
+use crate::script::LayoutLog;
+use crate::tmux::{self, Session};
 use crate::utils;
-use color_eyre::Result;
-use color_eyre::eyre::OptionExt;
+use color_eyre::Report;
 use color_eyre::eyre::WrapErr;
+use color_eyre::eyre::eyre;
 use color_eyre::owo_colors::OwoColorize;
 use itertools::Itertools;
+use std::error;
+use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+/// Errors of [`SessionManager`]. The cases worth handling on their own get a variant, failures of
+/// the lower layers are passed through
+#[derive(Debug)]
+pub enum Error {
+    /// There's no layout with this name
+    NotFound(String),
+    /// A running session already has the name of the layout being created with `strict` set
+    SessionRunning(String),
+    NoLastSession,
+    /// No repo in the search roots goes by this name
+    RepoNotFound(String),
+    /// Multiple repos go by the name, their paths are the candidates
+    AmbiguousRepo(String, Vec<String>),
+    /// The layout named after the directory was created for another one, which is the path
+    RootMismatch(String, PathBuf),
+    Layout(layout::Error),
+    /// The layout file with this name failed to run
+    Script(String, Report),
+    Tmux(tmux::Error),
+    Other(Report),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(name) => write!(f, "session not found: {name}"),
+            Self::SessionRunning(name) => write!(
+                f,
+                "a running session with name: {name}, already exists. Switching would attach to it instead of loading the layout"
+            ),
+            Self::NoLastSession => write!(f, "no last session saved"),
+            Self::RepoNotFound(name) => {
+                write!(f, "no repo named: {name}, found in the search roots")
+            }
+            Self::AmbiguousRepo(name, candidates) => write!(
+                f,
+                "multiple repos are named: {name}, pass one of their paths instead:\n{}",
+                candidates.join("\n")
+            ),
+            Self::RootMismatch(name, root) => write!(
+                f,
+                "layout: {name}, was created for another directory: {root:?}. Create one with a different name and switch to it by name instead"
+            ),
+            Self::Script(name, _) => {
+                write!(f, "an error occured while exucting the layout file: {name}")
+            }
+            // the wrapped errors are shown as they are
+            Self::Layout(e) => write!(f, "{e}"),
+            Self::Tmux(e) => write!(f, "{e}"),
+            Self::Other(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Layout(e) => e.source(),
+            Self::Script(_, e) => Some(e.as_ref()),
+            Self::Tmux(e) => e.source(),
+            Self::Other(e) => e.chain().nth(1),
+            _ => None,
+        }
+    }
+}
+
+impl From<layout::Error> for Error {
+    fn from(value: layout::Error) -> Self {
+        Error::Layout(value)
+    }
+}
+
+impl From<tmux::Error> for Error {
+    fn from(value: tmux::Error) -> Self {
+        Error::Tmux(value)
+    }
+}
+
+impl From<Report> for Error {
+    fn from(value: Report) -> Self {
+        Error::Other(value)
+    }
+}
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
 fn layout_from_options(
     name: Option<String>,
     path: &Path,
     layout_mgr: &LayoutManager,
+    config: &Config,
 ) -> Result<Layout> {
     let name = match name {
         Some(name) => LayoutName::try_new(name, layout_mgr)?,
-        None => LayoutName::try_from_path(path, layout_mgr)?,
+        // only plain basenames are told apart by the parent directories
+        None => match repo_search::repo_name(config, path) {
+            Some(name) if path.is_dir() => LayoutName::try_new(name, layout_mgr)?,
+            _ => LayoutName::try_from_path(path, layout_mgr)?,
+        },
     };
     Ok(Layout::new(name))
 }
 
+fn running_sessions() -> Result<Vec<String>> {
+    Ok(Session::list_sessions()?)
+}
+
+// The recently used repos only help the search along
+fn warn_unrecorded(recorded: color_eyre::Result<()>, path: &Path) {
+    if let Err(e) = recorded {
+        eprintln!(
+            "{}: failed to record {path:?} as recently used: {e}",
+            "warning".yellow().bold()
+        );
+    }
+}
+
 pub enum SwitchTarget {
     LastSession,
     Session(String),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct CreateSessionOptions {
     pub path: PathBuf,
     pub name: Option<String>,
     pub disable_editor: bool,
     pub machine_readable: bool,
+    pub strict: bool,
+    /// Leave the layout empty, as if templates were disabled in the config
+    pub empty: bool,
+    /// Use the built-in template even if a custom one exists
+    pub default_template: bool,
 }
 
 impl From<CreateSessionOptions> for CreateLayoutOptions {
     fn from(value: CreateSessionOptions) -> Self {
         Self {
             disable_editor: value.disable_editor,
+            empty: value.empty,
+            default_template: value.default_template,
         }
     }
 }
 
+/// Information about a layout created by [`SessionManager::create`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CreatedSession {
+    /// Name of the layout and of the session it spawns
+    pub name: String,
+    /// Path of the layout file
+    pub path: PathBuf,
+}
+
 pub use list_sessions::Options as ListSessionsOptions;
 
+// Snapshot of the sessions tmux knows about, fetched once per command since every query spawns
+// a tmux process
+struct TmuxState {
+    running_sessions: Vec<String>,
+    active_session: Option<String>,
+}
+
+impl TmuxState {
+    fn new(running_sessions: Vec<String>, active_session: Option<String>) -> Self {
+        Self {
+            running_sessions,
+            active_session,
+        }
+    }
+
+    fn fetch() -> Result<Self> {
+        let states = Session::session_states()?;
+        let active_session = states
+            .iter()
+            .find(|(_, attached)| *attached)
+            .map(|(name, _)| name.clone());
+        let running_sessions = states.into_iter().map(|(name, _)| name).collect();
+        Ok(Self::new(running_sessions, active_session))
+    }
+
+    // Only the session of the client celeris runs in is known, a client passed explicitly might
+    // be on any other one
+    fn attached_to(&self, tmux_name: &str, client: Option<&str>) -> bool {
+        client.is_none() && self.active_session.as_deref() == Some(tmux_name)
+    }
+}
+
 pub struct SessionManager {
     layout_mgr: LayoutManager,
+    config: Arc<Config>,
     dir_mgr: Arc<DirectoryManager>,
 }
 
@@ -60,35 +215,163 @@ impl SessionManager {
     pub fn new(config: Arc<Config>, dir_mgr: Arc<DirectoryManager>) -> Result<Self> {
         Ok(Self {
             layout_mgr: LayoutManager::new(Arc::clone(&config), Arc::clone(&dir_mgr))?,
+            config,
             dir_mgr,
         })
     }
 
+    /// Picks up the layouts again from the layouts directory. Meant for a long-lived session
+    /// manager when layouts get created or removed behind its back, e.g. from another terminal
+    pub fn reload(&mut self) -> Result<()> {
+        self.layout_mgr = LayoutManager::new(Arc::clone(&self.config), Arc::clone(&self.dir_mgr))?;
+        Ok(())
+    }
+
     fn layout(&self, name: &str) -> Result<&Layout> {
-        Ok(self
-            .layout_mgr
-            .layout(&name)
-            .ok_or_eyre(format!("session not found: {}", name))?)
+        self.layout_mgr
+            .layout(name)
+            .ok_or_else(|| Error::NotFound(name.to_owned()))
     }
 
-    pub fn create(&mut self, opts: CreateSessionOptions) -> Result<()> {
+    /// Checks if a layout with the given name exists
+    ///
+    /// ```no_run
+    /// # use celeris::{Config, DirectoryManager, SessionManager};
+    /// # use std::sync::Arc;
+    /// # fn main() -> color_eyre::Result<()> {
+    /// let dir_mgr = Arc::new(DirectoryManager::builder().build()?);
+    /// let config = Arc::new(Config::new(&dir_mgr)?);
+    /// let session_manager = SessionManager::new(config, dir_mgr)?;
+    /// if !session_manager.layout_exists("dotfiles") {
+    ///     eprintln!("no layout for dotfiles yet");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn layout_exists(&self, name: &str) -> bool {
+        self.layout_mgr.layout(name).is_some()
+    }
+
+    /// Names of the defined layouts, as their sessions would be named. Unlike [`SessionManager::list`]
+    /// tmux isn't asked about running sessions, so it's cheap
+    ///
+    /// ```no_run
+    /// # use celeris::{Config, DirectoryManager, SessionManager};
+    /// # use std::sync::Arc;
+    /// # fn main() -> color_eyre::Result<()> {
+    /// let dir_mgr = Arc::new(DirectoryManager::builder().build()?);
+    /// let config = Arc::new(Config::new(&dir_mgr)?);
+    /// let session_manager = SessionManager::new(config, dir_mgr)?;
+    /// for layout in session_manager.list_layouts() {
+    ///     println!("{layout}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn list_layouts(&self) -> Vec<String> {
+        self.layout_mgr.list().into_iter().cloned().collect()
+    }
+
+    /// Checks if a tmux session with the given name is running, regardless of whether it was
+    /// spawned from a layout
+    ///
+    /// ```no_run
+    /// # use celeris::{Config, DirectoryManager, SessionManager};
+    /// # use std::sync::Arc;
+    /// # fn main() -> color_eyre::Result<()> {
+    /// let dir_mgr = Arc::new(DirectoryManager::builder().build()?);
+    /// let config = Arc::new(Config::new(&dir_mgr)?);
+    /// let session_manager = SessionManager::new(config, dir_mgr)?;
+    /// if session_manager.session_running("dotfiles")? {
+    ///     println!("dotfiles is running");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn session_running(&self, name: &str) -> Result<bool> {
+        let name = LayoutName::tmux_safe(name);
+        Ok(running_sessions()?.contains(&name))
+    }
+
+    pub fn create(&mut self, opts: CreateSessionOptions) -> Result<CreatedSession> {
         let path = utils::expand_path(&opts.path)?;
-        let layout = layout_from_options(opts.name.clone(), &path, &self.layout_mgr)?;
-        let name = layout.tmux_name().to_owned();
-        self.layout_mgr
-            .create(layout, &path, opts.clone().into())
-            .wrap_err("failed to create layout file")?;
+        let layout = layout_from_options(opts.name.clone(), &path, &self.layout_mgr, &self.config)?;
+        let created = CreatedSession {
+            name: layout.tmux_name().to_owned(),
+            path: layout.storage_path(self.dir_mgr.layouts_dir())?,
+        };
+        Self::check_running_collision(&created.name, opts.strict)?;
+        self.layout_mgr.create(layout, &path, opts.clone().into())?;
+        // the layout is there already, so this isn't worth failing over
+        warn_unrecorded(
+            repo_search::record_recent(&self.config, &self.dir_mgr, &path),
+            &path,
+        );
+        warn_unrecorded(
+            repo_search::record_visit(&self.config, &self.dir_mgr, &path),
+            &path,
+        );
         match opts.machine_readable {
-            true => println!("{name}"),
+            true => println!("{}", created.name),
             false => eprintln!(
-                "{}: Created session with name: {name}",
-                "info".green().bold()
+                "{}: Created session with name: {}",
+                "info".green().bold(),
+                created.name
             ),
         };
+        Ok(created)
+    }
+
+    /// Finds a repo in the search roots by its directory name, its name in the configured
+    /// [`crate::NameStyle`], or by its path as [`crate::search`] prints it. Fails if there are
+    /// multiple repos going by the name
+    pub fn find_repo(&self, name: &str) -> Result<PathBuf> {
+        let repos = repo_search::search_with_recent(&self.config, &self.dir_mgr)?;
+        let candidates = match repos.iter().find(|repo| *repo == name) {
+            Some(exact) => vec![exact],
+            None => repos
+                .iter()
+                .filter(|repo| {
+                    Path::new(repo).file_name().is_some_and(|file| file == name)
+                        || repo_search::repo_name(&self.config, Path::new(repo))
+                            .is_some_and(|repo_name| repo_name == name)
+                })
+                .collect_vec(),
+        };
+        match candidates[..] {
+            [] => Err(Error::RepoNotFound(name.to_owned())),
+            [repo] => Ok(utils::expand_path(Path::new(repo))?),
+            _ => Err(Error::AmbiguousRepo(
+                name.to_owned(),
+                candidates.into_iter().cloned().collect(),
+            )),
+        }
+    }
+
+    // A layout named the same as an unrelated running session would get shadowed by it on switch
+    fn check_running_collision(name: &str, strict: bool) -> Result<()> {
+        if !running_sessions()?.iter().any(|session| session == name) {
+            return Ok(());
+        }
+
+        if strict {
+            return Err(Error::SessionRunning(name.to_owned()));
+        }
+        eprintln!(
+            "{}: a running session with name: {name}, already exists. Switching will attach to it until it's killed",
+            "warning".yellow().bold()
+        );
         Ok(())
     }
 
-    pub fn create_all(&mut self, paths: Vec<PathBuf>) -> Result<()> {
+    /// Creates layouts for all of the paths. `progress` gets called with a "created N/M: <name>"
+    /// message after each layout is created, pass `|_| {}` to ignore it. Returns the names of the
+    /// created layouts
+    pub fn create_all(
+        &mut self,
+        paths: Vec<PathBuf>,
+        progress: impl FnMut(&str),
+    ) -> Result<Vec<String>> {
         let rooted_layouts = paths
             .into_iter()
             .map(|p| utils::expand_path(&p))
@@ -98,12 +381,12 @@ impl SessionManager {
             })
             .map(|layout_info| {
                 let (name, path) = layout_info?;
-                let layout = layout_from_options(Some(name), &path, &self.layout_mgr)?;
+                let layout =
+                    layout_from_options(Some(name), &path, &self.layout_mgr, &self.config)?;
                 Ok((layout, path))
             })
             .collect::<Result<Vec<_>>>()?;
-        self.layout_mgr.create_all(rooted_layouts)?;
-        Ok(())
+        Ok(self.layout_mgr.create_all(rooted_layouts, progress)?)
     }
 
     pub fn edit(&self, tmux_name: &str) -> Result<()> {
@@ -111,27 +394,73 @@ impl SessionManager {
         Ok(())
     }
 
-    pub fn switch(&self, target: SwitchTarget) -> Result<()> {
+    /// Opens all of the layouts in one editor invocation, e.g. the ones returned by
+    /// [`SessionManager::create_all`]
+    pub fn edit_all(&self, tmux_names: &[String]) -> Result<()> {
+        let tmux_names = tmux_names.iter().map(String::as_str).collect_vec();
+        self.layout_mgr.edit_all(&tmux_names)?;
+        Ok(())
+    }
+
+    /// Switches `client`, e.g. its tty, to the target instead of the current one if given
+    pub fn switch(&self, target: SwitchTarget, client: Option<&str>) -> Result<()> {
+        let state = TmuxState::fetch()?;
         match target {
-            SwitchTarget::LastSession => self.switch_last()?,
-            SwitchTarget::Session(name) => self.switch_core(&name)?,
+            SwitchTarget::LastSession => self.switch_last(&state, client)?,
+            SwitchTarget::Session(name) => self.switch_core(&name, &state, client)?,
+        }
+        Ok(())
+    }
+
+    /// Switches to the session for a directory, named after it. If there's no layout or running
+    /// session going by that name, a layout is created from the template first. A layout going by
+    /// that name which was created for another directory is an error
+    pub fn switch_path(&mut self, path: &Path, client: Option<&str>) -> Result<()> {
+        let state = TmuxState::fetch()?;
+        let path = utils::expand_path(path)?;
+        if !path.is_dir() {
+            return Err(eyre!("not a directory: {path:?}").into());
+        }
+        let name = LayoutName::tmux_safe(&utils::file_name(&path)?);
+        if !self.layout_exists(&name) && !state.running_sessions.contains(&name) {
+            self.create(CreateSessionOptions {
+                path,
+                name: Some(name.clone()),
+                disable_editor: true,
+                ..Default::default()
+            })?;
+        } else {
+            self.check_root(&name, &path)?;
+            warn_unrecorded(
+                repo_search::record_visit(&self.config, &self.dir_mgr, &path),
+                &path,
+            );
+        }
+        self.switch_core(&name, &state, client)
+    }
+
+    // Sessions without a layout and layouts without a recorded root can't be told apart from
+    // the ones for the directory, so they're used as they are
+    fn check_root(&self, tmux_name: &str, path: &Path) -> Result<()> {
+        let Some(root) = self.layout_mgr.root(tmux_name)? else {
+            return Ok(());
+        };
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        if root != path {
+            return Err(Error::RootMismatch(tmux_name.to_owned(), root));
         }
         Ok(())
     }
 
-    fn switch_last(&self) -> Result<()> {
-        let last = self
-            .layout_mgr
-            .get_last()?
-            .ok_or_eyre("no last session saved")?;
-        self.switch_core(&last)?;
+    fn switch_last(&self, state: &TmuxState, client: Option<&str>) -> Result<()> {
+        let last = self.layout_mgr.get_last()?.ok_or(Error::NoLastSession)?;
+        self.switch_core(&last, state, client)?;
         Ok(())
     }
 
-    fn switch_core(&self, tmux_name: &str) -> Result<()> {
-        let tmux_name = tmux_name.to_owned();
-        let active_session = Session::active_name().wrap_err("failed to get active sesion")?;
-        if Some(&tmux_name) == active_session.as_ref() {
+    fn switch_core(&self, tmux_name: &str, state: &TmuxState, client: Option<&str>) -> Result<()> {
+        let tmux_name = LayoutName::tmux_safe(tmux_name);
+        if state.attached_to(&tmux_name, client) {
             eprintln!(
                 "{}: session with that name is already attached. Aborting switch",
                 "info".green().bold()
@@ -139,34 +468,82 @@ impl SessionManager {
             return Ok(());
         }
 
-        let running_sessions = Self::running_sessions(active_session.as_ref())?;
-        self.layout_mgr
-            .save_if_layout(&tmux_name)
-            .wrap_err("failed to save session name for later use")?;
-        if running_sessions.contains(&tmux_name) {
-            let session = Session::from(&tmux_name)?;
-            session.attach()?;
+        self.layout_mgr.save_if_layout(&tmux_name)?;
+        if state.running_sessions.contains(&tmux_name) {
+            if !self.config.no_attach {
+                Session::from(&tmux_name)?.attach_with(client)?;
+            }
         } else {
-            self.run(&tmux_name)?;
+            self.run(&tmux_name, !self.config.no_attach, client)?;
         }
         Ok(())
     }
 
-    fn running_sessions(active_session: Option<&String>) -> Result<Vec<String>> {
-        let running_sessions =
-            Session::list_sessions().wrap_err("failed to get running sessions")?;
-        Ok(running_sessions
-            .into_iter()
-            .filter(|s| Some(s) != active_session)
-            .collect())
+    /// Runs the layouts without attaching, leaving their sessions ready in the background. All of
+    /// the layouts are run if `names` is empty, the ones with a running session are skipped. A
+    /// failing layout doesn't stop the rest from being run
+    pub fn warm(&self, names: Vec<String>) -> Result<()> {
+        let names = match names.is_empty() {
+            true => self.list_layouts(),
+            false => names
+                .iter()
+                .map(|name| LayoutName::tmux_safe(name))
+                .collect_vec(),
+        };
+        let running_sessions = running_sessions()?;
+        let mut failed = Vec::new();
+        for name in names {
+            if running_sessions.contains(&name) {
+                continue;
+            }
+            if let Err(e) = self.run(&name, false, None) {
+                eprintln!("{}: {e:?}", "warning".yellow().bold());
+                failed.push(name);
+            }
+        }
+        match failed.is_empty() {
+            true => Ok(()),
+            false => Err(eyre!("failed to warm: {}", failed.join(", ")).into()),
+        }
     }
 
-    fn run(&self, tmux_name: &str) -> Result<()> {
+    fn run(&self, tmux_name: &str, attach: bool, client: Option<&str>) -> Result<()> {
         let layout = self.layout(tmux_name)?;
-        script::run(layout, self.dir_mgr.layouts_dir()).wrap_err(format!(
-            "an error occured while exucting the layout file: {tmux_name}"
-        ))?;
-        Ok(())
+        script::run(
+            layout,
+            self.dir_mgr.layouts_dir(),
+            self.layout_log(),
+            attach,
+            client,
+        )
+        .map_err(|e| Error::Script(tmux_name.to_owned(), e))
+    }
+
+    fn layout_log(&self) -> LayoutLog {
+        const LAYOUT_LOG_FILE: &str = "layout.log";
+        LayoutLog::new(
+            self.config
+                .layout_log
+                .clone()
+                .unwrap_or_else(|| self.dir_mgr.cache_dir().join(LAYOUT_LOG_FILE)),
+        )
+    }
+
+    /// Runs the layout with its tmux commands recorded instead of run. Returns the steps the
+    /// layout would take, one per line
+    pub fn validate(&self, tmux_name: &str) -> Result<String> {
+        let layout = self.layout(tmux_name)?;
+        let plan = script::validate(layout, self.dir_mgr.layouts_dir())
+            .wrap_err(format!("layout file: {tmux_name}, is invalid"))?;
+        Ok(plan.join("\n"))
+    }
+
+    /// Like [`SessionManager::validate`], but returns the tmux commands the layout would run as
+    /// a shell script, which can be inspected or run elsewhere
+    pub fn print_commands(&self, tmux_name: &str) -> Result<String> {
+        let layout = self.layout(tmux_name)?;
+        Ok(script::print_commands(layout, self.dir_mgr.layouts_dir())
+            .wrap_err(format!("layout file: {tmux_name}, is invalid"))?)
     }
 
     pub fn remove(&mut self, names: Vec<String>) -> Result<()> {
@@ -176,20 +553,23 @@ impl SessionManager {
             // please don't kill me
             .collect::<Result<Vec<_>>>()?;
 
-        self.layout_mgr
-            .remove_all(layouts.iter().collect_vec())
-            .wrap_err_with(|| format!("failed to remove layout/s"))?;
+        self.layout_mgr.remove_all(layouts.iter().collect_vec())?;
         Ok(())
     }
     pub fn list(&self, options: ListSessionsOptions) -> Result<String> {
-        Ok(list_sessions::run(&self.layout_mgr, options)?)
+        Ok(list_sessions::run(
+            &self.layout_mgr,
+            &TmuxState::fetch()?,
+            options,
+        )?)
     }
 }
 
 mod list_sessions {
+    use super::TmuxState;
     use crate::layout::LayoutManager;
-    use crate::tmux::Session;
     use color_eyre::Result;
+    use color_eyre::eyre::eyre;
     use itertools::Itertools;
 
     pub struct Options {
@@ -197,48 +577,40 @@ mod list_sessions {
         pub include_active: bool,
         pub exclude_running: bool,
         pub only_running: bool,
+        // only the number of sessions which passed the filters
+        pub count: bool,
+        // separated with NUL instead of newlines, for names which contain newlines
+        pub null: bool,
     }
 
-    struct ExcludeInfo {
-        running_sessions: Vec<String>,
-        active_session: Option<String>,
-    }
-
-    impl ExcludeInfo {
-        fn new(running_sessions: Vec<String>, active_session: Option<String>) -> Self {
-            Self {
-                running_sessions,
-                active_session,
-            }
+    pub fn run(layout_mgr: &LayoutManager, state: &TmuxState, opts: Options) -> Result<String> {
+        if opts.only_running && opts.exclude_running {
+            return Err(eyre!(
+                "only_running and exclude_running are mutually exclusive"
+            ));
         }
-    }
 
-    pub fn run(layout_mgr: &LayoutManager, opts: Options) -> Result<String> {
         let layouts = layout_mgr.list().into_iter().map(ToOwned::to_owned);
-        let running_sessions = Session::list_sessions()?;
-        let sessions = layouts.chain(running_sessions.clone().into_iter());
-        let active_session = Session::active_name()?;
-
-        let exclude_info = ExcludeInfo::new(running_sessions, active_session.clone());
+        let sessions = layouts.chain(state.running_sessions.iter().cloned());
         let sessions = sessions
-            .filter(|name| exclude(name, &exclude_info, &opts))
+            .filter(|name| exclude(name, state, &opts))
             .map(|session| match session {
-                active if active_session.as_ref() == Some(&session) => format!("{active}*"),
+                active if state.active_session.as_ref() == Some(&session) => format!("{active}*"),
                 _ => session,
             })
             .collect_vec();
-        let sessions = sessions
-            .into_iter()
-            .sorted()
-            .dedup()
-            .join(match opts.tmux_format {
-                true => " ",
-                false => "\n",
-            });
-        Ok(sessions)
+        let mut sessions = sessions.into_iter().sorted().dedup();
+        if opts.count {
+            return Ok(sessions.count().to_string());
+        }
+        Ok(sessions.join(match (opts.tmux_format, opts.null) {
+            (true, _) => " ",
+            (false, true) => "\0",
+            (false, false) => "\n",
+        }))
     }
 
-    fn exclude(session_name: &str, info: &ExcludeInfo, opts: &Options) -> bool {
+    fn exclude(session_name: &str, info: &TmuxState, opts: &Options) -> bool {
         if opts.only_running {
             return info.running_sessions.contains(&session_name.to_owned());
         }
@@ -256,4 +628,76 @@ mod list_sessions {
 
         true
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn opts() -> Options {
+            Options {
+                tmux_format: false,
+                include_active: false,
+                exclude_running: false,
+                only_running: false,
+                count: false,
+                null: false,
+            }
+        }
+
+        fn info() -> TmuxState {
+            TmuxState::new(
+                vec!["running".to_owned(), "active".to_owned()],
+                Some("active".to_owned()),
+            )
+        }
+
+        #[test]
+        fn only_running() {
+            let opts = Options {
+                only_running: true,
+                ..opts()
+            };
+            assert!(exclude("running", &info(), &opts));
+            assert!(exclude("active", &info(), &opts));
+            assert!(!exclude("layout", &info(), &opts));
+        }
+
+        #[test]
+        fn exclude_running() {
+            let opts = Options {
+                exclude_running: true,
+                ..opts()
+            };
+            assert!(!exclude("running", &info(), &opts));
+            assert!(!exclude("active", &info(), &opts));
+            assert!(exclude("layout", &info(), &opts));
+        }
+
+        #[test]
+        fn include_active() {
+            assert!(!exclude("active", &info(), &opts()));
+            let opts = Options {
+                include_active: true,
+                ..opts()
+            };
+            assert!(exclude("active", &info(), &opts));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attached_to() {
+        let state = TmuxState::new(vec!["foo".to_owned()], Some("foo".to_owned()));
+        assert!(state.attached_to("foo", None));
+        assert!(!state.attached_to("bar", None));
+        // the other client might be anywhere, so it's switched regardless
+        assert!(!state.attached_to("foo", Some("/dev/pts/1")));
+
+        let state = TmuxState::new(vec!["foo".to_owned()], None);
+        assert!(!state.attached_to("foo", None));
+    }
 }
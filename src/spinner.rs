@@ -0,0 +1,49 @@
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Spinner drawn on stderr while a slow operation runs, the line is cleared once it's dropped so
+/// that it doesn't end up mixed with the output
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    pub fn start(message: String) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = thread::spawn({
+            let stop = Arc::clone(&stop);
+            move || {
+                for frame in Self::FRAMES.iter().cycle() {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    // the cursor is moved back to the start, so that warnings printed meanwhile
+                    // overwrite the spinner instead of being appended to it
+                    let _ = write!(io::stderr(), "\r\x1b[2K{frame} {message}\r");
+                    thread::sleep(Self::INTERVAL);
+                }
+            }
+        });
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        let _ = write!(io::stderr(), "\r\x1b[2K");
+    }
+}
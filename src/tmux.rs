@@ -5,16 +5,18 @@ mod session;
 mod tests;
 mod window;
 
-use color_eyre::{
-    Result,
-    eyre::{Context, eyre},
-};
 use std::{
+    cell::RefCell,
     env::{self, VarError},
+    error,
+    fmt::Display,
+    io,
+    rc::Rc,
     str,
+    time::Duration,
 };
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
@@ -22,6 +24,113 @@ pub use pane::{Direction, Pane, SplitBuilder, SplitSize};
 pub use session::{Session, SessionBuilder};
 pub use window::{Window, WindowBuilder};
 
+#[derive(Debug)]
+pub enum Error {
+    ServerNotRunning,
+    /// The session, window or pane isn't there (anymore)
+    TargetNotFound(String),
+    /// A session with this name is running already
+    AlreadyExists(String),
+    /// tmux exited with a failure, `args` are the arguments it was run with
+    CommandFailed {
+        args: Vec<String>,
+        stderr: String,
+    },
+    TmuxNotInstalled,
+    /// tmux printed something other than what was asked for
+    ParseError(String),
+    /// Spawning or waiting for tmux failed for another reason than it not being installed
+    Spawn(io::Error),
+    /// Describes what contained the invalid unicode
+    InvalidUnicode(String),
+    RootNotFound(PathBuf),
+    RootNotADirectory(PathBuf),
+    /// Options of a builder which tmux can't act on, e.g. a degenerate split size
+    InvalidOption(String),
+    /// Attaching or switching the client failed, with the message of tmux
+    AttachFailed {
+        session: String,
+        message: String,
+    },
+    /// The pattern didn't show up in the pane in time
+    Timeout {
+        target: String,
+        pattern: String,
+        timeout: Duration,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ServerNotRunning => write!(f, "the tmux server isn't running"),
+            Self::TargetNotFound(target) => write!(f, "tmux target doesn't exist: {target}"),
+            Self::AlreadyExists(session) => {
+                write!(f, "session with name: {session}, already exists")
+            }
+            Self::CommandFailed { args, stderr } => {
+                write!(f, "tmux {}: failed with: {}", args.join(" "), stderr.trim())
+            }
+            Self::TmuxNotInstalled => write!(f, "tmux not found, is it installed?"),
+            Self::ParseError(comment) => write!(f, "failed to parse tmux output: {comment}"),
+            Self::Spawn(_) => write!(f, "failed to execute tmux"),
+            Self::InvalidUnicode(comment) => write!(f, "invalid unicode in {comment}"),
+            Self::RootNotFound(path) => write!(f, "Root path doesn't exist: {path:?}"),
+            Self::RootNotADirectory(path) => write!(f, "root must be a directory: {path:?}"),
+            Self::InvalidOption(comment) => write!(f, "{comment}"),
+            Self::AttachFailed { session, message } => {
+                write!(f, "failed to attach to session: {session}: {message}")
+            }
+            Self::Timeout {
+                target,
+                pattern,
+                timeout,
+            } => write!(
+                f,
+                "timed out after {}ms waiting for: {pattern:?} in pane: {target}",
+                timeout.as_millis()
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Spawn(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        match value.kind() {
+            io::ErrorKind::NotFound => Error::TmuxNotInstalled,
+            _ => Error::Spawn(value),
+        }
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+fn command_args(command: &Command) -> Vec<String> {
+    command
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+// What tmux says when there's no server on the socket, or the socket is stale
+fn is_server_gone(stderr: &str) -> bool {
+    stderr.starts_with("no server running") || stderr.starts_with("error connecting to")
+}
+
+fn path_to_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| Error::InvalidUnicode(format!("path: {path:?}")))
+}
+
 pub fn tmux() -> Result<Command> {
     let mut command = Command::new("tmux");
     match (
@@ -31,48 +140,130 @@ pub fn tmux() -> Result<Command> {
         (Ok(ref name), Err(VarError::NotPresent)) => command.args(["-L", name]),
         (Err(VarError::NotPresent), Ok(ref path)) => command.args(["-S", path]),
         (Err(VarError::NotUnicode(err)), _) | (_, Err(VarError::NotUnicode(err))) => {
-            return Err(eyre!(
-                "tmux socket target contains invalid unicode: {err:?}"
-            ));
+            return Err(Error::InvalidUnicode(format!(
+                "the tmux socket target: {err:?}"
+            )));
         }
         _ => return Ok(command),
     };
     Ok(command)
 }
 
+/// Runs the tmux commands celeris assembles. Swappable with [`with_executor`], so that assembling
+/// the commands and parsing their output can be tested without a tmux server
+pub trait TmuxExecutor {
+    /// Returns the stdout of the command, a failing exit code is an error
+    fn execute(&self, command: &mut Command) -> Result<String>;
+
+    /// Only reports whether the command succeeded
+    fn succeeds(&self, command: &mut Command) -> Result<bool>;
+
+    /// Whether the path is made of output which is only known once the commands run, e.g. when
+    /// they're recorded instead. Such paths can't be checked up front
+    fn known_when_run(&self, _path: &Path) -> bool {
+        false
+    }
+}
+
+/// The default executor, which spawns tmux
+pub struct ProcessExecutor;
+
+impl TmuxExecutor for ProcessExecutor {
+    fn execute(&self, command: &mut Command) -> Result<String> {
+        let output = command.output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            if is_server_gone(&stderr) {
+                return Err(Error::ServerNotRunning);
+            }
+            return Err(Error::CommandFailed {
+                args: command_args(command),
+                stderr,
+            });
+        }
+        String::from_utf8(output.stdout)
+            .map_err(|_| Error::InvalidUnicode("the output of tmux".to_owned()))
+    }
+
+    fn succeeds(&self, command: &mut Command) -> Result<bool> {
+        Ok(command.status()?.success())
+    }
+}
+
+thread_local! {
+    static EXECUTOR: RefCell<Rc<dyn TmuxExecutor>> = RefCell::new(Rc::new(ProcessExecutor));
+}
+
+// Puts the previous executor back even if the closure panics
+struct ExecutorGuard(Option<Rc<dyn TmuxExecutor>>);
+
+impl Drop for ExecutorGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            EXECUTOR.set(previous);
+        }
+    }
+}
+
+/// Runs `f` with the tmux commands issued on this thread going through `executor`. Attaching
+/// always spawns tmux, since it hands over the terminal
+pub fn with_executor<T>(executor: Rc<dyn TmuxExecutor>, f: impl FnOnce() -> T) -> T {
+    let _guard = ExecutorGuard(Some(EXECUTOR.replace(executor)));
+    f()
+}
+
+fn executor() -> Rc<dyn TmuxExecutor> {
+    EXECUTOR.with_borrow(Rc::clone)
+}
+
 pub trait TmuxExecuteExt {
     fn execute(&mut self) -> Result<String>;
 }
 
 impl TmuxExecuteExt for Command {
     fn execute(&mut self) -> Result<String> {
-        let output = self
-            .output()
-            .wrap_err_with(|| format!("failed to execute tmux command: {:?}", self))?;
-
-        if !output.status.success() {
-            return Err(eyre!(
-                "Command: {:?}: {}",
-                self,
-                str::from_utf8(&output.stderr).wrap_err_with(|| "Tmux returned invalid utf-8")?
-            ));
-        }
-        Ok(String::from_utf8(output.stdout).wrap_err_with(|| "Tmux returned invalid utf-8")?)
+        executor().execute(self)
     }
 }
 
 pub fn server_running() -> Result<bool> {
     let mut command = tmux()?;
-    command.args(["display-message", "-p", "#{socket_path}"]);
-
-    let status = command
+    command
+        .args(["display-message", "-p", "#{socket_path}"])
         .stdin(Stdio::null())
         .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .wrap_err_with(|| format!("failed to execute tmux command: {:?}", command))?;
+        .stderr(Stdio::null());
+    executor().succeeds(&mut command)
+}
+
+/// Whether celeris talks to a server of its own through `CELERIS_TMUX_SOCKET_NAME` or
+/// `CELERIS_TMUX_SOCKET_PATH` rather than the default one
+pub fn custom_socket() -> bool {
+    env::var_os("CELERIS_TMUX_SOCKET_NAME").is_some()
+        || env::var_os("CELERIS_TMUX_SOCKET_PATH").is_some()
+}
 
-    Ok(status.success())
+/// Kills the server celeris talks to along with all of its sessions. Nothing to do if it isn't
+/// running
+pub fn kill_server() -> Result<()> {
+    if !server_running()? {
+        return Ok(());
+    }
+    tmux()?.arg("kill-server").execute()?;
+    Ok(())
+}
+
+// Relative roots of windows and panes are resolved against the root of their session, which tmux
+// keeps as the session path
+fn resolve_root(target: &impl Target, path: PathBuf) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path);
+    }
+    let session_path = target
+        .targeted_command("display-message")?
+        .args(["-p", "#{session_path}"])
+        .execute()?;
+    Ok(PathBuf::from(session_path.trim()).join(path))
 }
 
 #[derive(Clone, Debug)]
@@ -92,8 +283,15 @@ pub struct Root(RootOptions);
 
 impl Root {
     pub fn custom(path: PathBuf) -> Result<Self> {
+        if executor().known_when_run(&path) {
+            return Ok(Self(RootOptions::Custom(path)));
+        }
         if !path.exists() {
-            return Err(eyre!("Root path doesn't exist: {path:?}"));
+            return Err(Error::RootNotFound(path));
+        }
+        // tmux would only fail later on with a vague error
+        if !path.is_dir() {
+            return Err(Error::RootNotADirectory(path));
         }
 
         Ok(Self(RootOptions::Custom(path)))
@@ -116,21 +314,17 @@ pub trait Target {
     fn get(&self) -> &str;
 
     fn target_exists(&self) -> Result<bool> {
-        let has_session_status = tmux()?
+        let mut command = tmux()?;
+        command
             .args(["has-session", "-t", self.get()])
             .stderr(Stdio::null())
-            .stdout(Stdio::null())
-            .status()
-            .wrap_err_with(|| "has-session failed to execute")?;
-        Ok(has_session_status.success())
+            .stdout(Stdio::null());
+        executor().succeeds(&mut command)
     }
 
     fn targeted_command(&self, command: &str) -> Result<Command> {
         if !self.target_exists()? {
-            return Err(eyre!(
-                "tried to execute a commend: {command} with a non-existing target: {}",
-                self.get()
-            ));
+            return Err(Error::TargetNotFound(self.get().to_owned()));
         }
         let mut tmux = tmux()?;
         tmux.args([command, "-t", self.get()]);
@@ -217,7 +411,7 @@ impl PaneTarget {
         Self {
             target: format!("{}:{}.{}", sibling.session_id, sibling.window_id, pane_id),
             pane_id: pane_id.to_owned(),
-            window_id: sibling.session_id.clone(),
+            window_id: sibling.window_id.clone(),
             session_id: sibling.session_id.clone(),
         }
     }
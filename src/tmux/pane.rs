@@ -1,8 +1,10 @@
-use crate::tmux::{self, PaneTarget, Root, RootOptions, Target, TmuxExecuteExt};
-use crate::utils;
-use color_eyre::{Result, eyre::eyre};
+use crate::tmux::{
+    self, Error, PaneTarget, Result, Root, RootOptions, Target, TmuxExecuteExt, path_to_str,
+};
 use std::path::PathBuf;
 use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Direction {
@@ -12,8 +14,35 @@ pub enum Direction {
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum SplitSize {
+    /// Percentage of the split pane, has to be within 1..=99
     Percentage(u8),
     Absolute(u32),
+    /// Fraction of the split pane, e.g. 1/3. Has to be strictly between 0 and 1. Resolved into an
+    /// absolute size, since tmux only understands whole percentages
+    Fraction {
+        numerator: u32,
+        denominator: u32,
+    },
+}
+
+impl SplitSize {
+    // 0 and 100 are degenerate, tmux would just produce a 1 cell pane on one of the sides
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            SplitSize::Percentage(percentage) if !(1..=99).contains(&percentage) => {
+                Err(Error::InvalidOption(format!(
+                    "split percentage has to be between 1 and 99, got: {percentage}"
+                )))
+            }
+            SplitSize::Fraction {
+                numerator,
+                denominator,
+            } if numerator == 0 || numerator >= denominator => Err(Error::InvalidOption(format!(
+                "split fraction has to be between 0 and 1 exclusive, got: {numerator}/{denominator}"
+            ))),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -51,7 +80,9 @@ impl SplitBuilder {
         Self { opts, ..self }
     }
 
+    /// Working directory of the pane, a relative path is relative to the root of the session
     pub fn root(self, path: PathBuf) -> Result<Self> {
+        let path = tmux::resolve_root(&self.sibling_target, path)?;
         let opts = SplitOptions {
             root: Root::custom(path)?,
             ..self.opts
@@ -73,12 +104,17 @@ impl SplitBuilder {
             return Ok(());
         };
 
+        size.validate()?;
         match size {
-            SplitSize::Percentage(percentage) if percentage <= 100 => {
+            SplitSize::Percentage(percentage) => {
                 options.extend(["-l".to_owned(), format!("{percentage}%")]);
             }
-            SplitSize::Percentage(percentage) => {
-                return Err(eyre!("Percentage amount above 100: {percentage}"));
+            SplitSize::Fraction {
+                numerator,
+                denominator,
+            } => {
+                let cells = self.sibling_size()? * u64::from(numerator) / u64::from(denominator);
+                options.extend(["-l".to_owned(), cells.max(1).to_string()]);
             }
             SplitSize::Absolute(absolute) => {
                 options.extend(["-l".to_owned(), absolute.to_string()])
@@ -88,9 +124,26 @@ impl SplitBuilder {
         Ok(())
     }
 
+    // the dimension of the sibling that is going to be split
+    fn sibling_size(&self) -> Result<u64> {
+        let format = match self.opts.direction {
+            Direction::Horizontal => "#{pane_width}",
+            Direction::Vertical => "#{pane_height}",
+        };
+        let output = self
+            .sibling_target
+            .targeted_command("display-message")?
+            .args(["-p", format])
+            .execute()?;
+        output
+            .trim()
+            .parse()
+            .map_err(|_| Error::ParseError(format!("pane size: {output}")))
+    }
+
     fn prepare_root(&self, options: &mut Vec<String>) -> Result<()> {
         let root = match self.opts.root.as_ref() {
-            RootOptions::Custom(path) => utils::path_to_string(path)?,
+            RootOptions::Custom(path) => path_to_str(path)?.to_owned(),
             RootOptions::Default => "#{pane_current_path}".to_owned(),
         };
 
@@ -128,6 +181,14 @@ impl PartialEq for SplitBuilder {
     }
 }
 
+// tmux splits commands on an argument ending with `;` unless it's escaped as `\;`
+fn escape_separator(argument: &str) -> String {
+    match argument.strip_suffix(';') {
+        Some(rest) => format!("{rest}\\;"),
+        None => argument.to_owned(),
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Pane {
     target: PaneTarget,
@@ -148,14 +209,93 @@ impl Pane {
         Ok(())
     }
 
+    /// Types the command into the pane verbatim and presses Enter. Nothing in it is interpreted by
+    /// tmux, so e.g. `;` or words that happen to be key names like `Enter` are typed as they are
     pub fn run_command(&self, command: &str) -> Result<()> {
+        let target = self.target().get().to_owned();
         self.target()
             .targeted_command("send-keys")?
-            .args([command, "Enter"])
+            .args(["-l", "--", &escape_separator(command), ";"])
+            .args(["send-keys", "-t", &target, "Enter"])
             .execute()?;
         Ok(())
     }
 
+    /// Like [`Pane::run_command`], but the pane stays around after its process exits instead of
+    /// closing, e.g. so that the scrollback of a crashed dev server started with `exec` can still
+    /// be read. Only `remain-on-exit` of this pane is set, the rest of the session is left alone
+    pub fn run_command_keep_alive(&self, command: &str) -> Result<()> {
+        self.set_remain_on_exit(true)?;
+        self.run_command(command)
+    }
+
+    /// Whether the pane stays around, dead, after its process exits. Set for this pane only
+    pub fn set_remain_on_exit(&self, remain: bool) -> Result<()> {
+        self.target()
+            .targeted_command("set-option")?
+            .args(["-p", "remain-on-exit", if remain { "on" } else { "off" }])
+            .execute()?;
+        Ok(())
+    }
+
+    /// Restarts the pane in place with `command`, or with the command it was started with. A
+    /// process which is still running gets killed
+    pub fn respawn(&self, command: Option<&str>) -> Result<()> {
+        self.target()
+            .targeted_command("respawn-pane")?
+            .arg("-k")
+            .args(command)
+            .execute()?;
+        Ok(())
+    }
+
+    /// Labels the pane, separately from the name of its window. The title is only shown with
+    /// tmux's `pane-border-status` turned on, otherwise it's just `#{pane_title}` for formats
+    pub fn set_title(&self, title: &str) -> Result<()> {
+        self.target()
+            .targeted_command("select-pane")?
+            .args(["-T", title])
+            .execute()?;
+        Ok(())
+    }
+
+    /// Sends the keys the way `tmux send-keys` interprets them, e.g. `C-c` or `Enter` are
+    /// keypresses rather than text to type. [`Pane::run_command`] is the one for commands
+    pub fn send_keys(&self, keys: &str) -> Result<()> {
+        self.target()
+            .targeted_command("send-keys")?
+            .arg(keys)
+            .execute()?;
+        Ok(())
+    }
+
+    /// Contents of the visible part of the pane
+    pub fn capture(&self) -> Result<String> {
+        self.target()
+            .targeted_command("capture-pane")?
+            .arg("-p")
+            .execute()
+    }
+
+    /// Polls the contents of the pane until `pattern` shows up in them, errors out after `timeout`
+    pub fn wait_for(&self, pattern: &str, timeout: Duration) -> Result<()> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(100);
+        let start = Instant::now();
+        loop {
+            if self.capture()?.contains(pattern) {
+                return Ok(());
+            }
+            if start.elapsed() >= timeout {
+                return Err(Error::Timeout {
+                    target: self.target.get().to_owned(),
+                    pattern: pattern.to_owned(),
+                    timeout,
+                });
+            }
+            thread::sleep(POLL_INTERVAL.min(timeout.saturating_sub(start.elapsed())));
+        }
+    }
+
     pub fn target(&self) -> &PaneTarget {
         &self.target
     }
@@ -165,10 +305,66 @@ impl Pane {
 mod tests {
     use super::*;
     use crate::tmux::Session;
-    use crate::tmux::tests::TESTING_SESSION;
+    use crate::tmux::tests::{MockExecutor, TESTING_SESSION};
+    use crate::tmux::{SessionTarget, with_executor};
     use crate::tmux::{Target, Window, tests::testing_session};
+    use crate::utils;
+    use color_eyre::Result;
     use std::env;
-    use std::{thread, time::Duration};
+    use std::rc::Rc;
+
+    fn split_builder(direction: Direction) -> SplitBuilder {
+        let target = SessionTarget::new("$1")
+            .window_target("@1")
+            .pane_target("%1");
+        SplitBuilder::new(target, direction)
+    }
+
+    #[test]
+    fn prepare_options() -> Result<()> {
+        assert_eq!(
+            split_builder(Direction::Vertical).prepare_options()?,
+            ["-c", "#{pane_current_path}"]
+        );
+
+        let root = env::temp_dir();
+        let builder = split_builder(Direction::Horizontal)
+            .size(SplitSize::Percentage(30))
+            .root(root.clone())?;
+        assert_eq!(
+            builder.prepare_options()?,
+            ["-l", "30%", "-c", &utils::path_to_string(&root)?]
+        );
+
+        let builder = split_builder(Direction::Horizontal).size(SplitSize::Absolute(42));
+        assert_eq!(builder.prepare_options()?[..2], ["-l", "42"]);
+
+        let builder = split_builder(Direction::Horizontal).size(SplitSize::Percentage(100));
+        assert!(builder.prepare_options().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn prepare_options_fraction() -> Result<()> {
+        let mock = Rc::new(MockExecutor::default());
+        // has-session of the sibling
+        mock.respond("");
+        mock.respond("90\n");
+        let options = with_executor(mock.clone(), || {
+            split_builder(Direction::Horizontal)
+                .size(SplitSize::Fraction {
+                    numerator: 1,
+                    denominator: 3,
+                })
+                .prepare_options()
+        })?;
+        assert_eq!(options[..2], ["-l", "30"]);
+        assert_eq!(
+            mock.commands()[1],
+            ["display-message", "-t", "$1:@1.%1", "-p", "#{pane_width}"]
+        );
+        Ok(())
+    }
 
     #[test]
     fn split() -> Result<()> {
@@ -210,7 +406,7 @@ mod tests {
         let pane = window
             .default_pane()
             .split(Direction::Horizontal)
-            .size(SplitSize::Percentage(0))
+            .size(SplitSize::Percentage(1))
             .build()?;
 
         let output = window
@@ -231,12 +427,57 @@ mod tests {
 
         assert_eq!(output.trim(), "1");
 
-        let _ = window
-            .default_pane()
+        [0, 100, 101].into_iter().for_each(|percentage| {
+            let _ = window
+                .default_pane()
+                .split(Direction::Horizontal)
+                .size(SplitSize::Percentage(percentage))
+                .build()
+                .expect_err("degenerate percentage should fail");
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn split_fraction_sized() -> Result<()> {
+        let session = testing_session()?;
+        let window = Window::builder(&session).build()?;
+        let sibling = window.default_pane();
+        let sibling_width = sibling
+            .target()
+            .targeted_command("display-message")?
+            .args(["-p", "#{pane_width}"])
+            .execute()?
+            .trim()
+            .parse::<u32>()?;
+        let pane = sibling
             .split(Direction::Horizontal)
-            .size(SplitSize::Percentage(101))
-            .build()
-            .unwrap_err();
+            .size(SplitSize::Fraction {
+                numerator: 1,
+                denominator: 4,
+            })
+            .build()?;
+
+        let output = pane
+            .target()
+            .targeted_command("display-message")?
+            .args(["-p", "#{pane_width}"])
+            .execute()?;
+        assert_eq!(output.trim(), (sibling_width / 4).max(1).to_string());
+
+        [(0, 4), (4, 4), (1, 0)]
+            .into_iter()
+            .for_each(|(numerator, denominator)| {
+                let _ = window
+                    .default_pane()
+                    .split(Direction::Horizontal)
+                    .size(SplitSize::Fraction {
+                        numerator,
+                        denominator,
+                    })
+                    .build()
+                    .expect_err("degenerate fraction should fail");
+            });
         Ok(())
     }
 
@@ -303,6 +544,137 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn set_title() -> Result<()> {
+        let session = testing_session()?;
+        let window = Window::builder(&session).build()?;
+        let pane = window.default_pane();
+        pane.set_title("logs")?;
+        let output = pane
+            .target()
+            .targeted_command("display-message")?
+            .args(["-p", "#{pane_title}"])
+            .execute()?;
+        assert_eq!(output.trim(), "logs");
+        Ok(())
+    }
+
+    #[test]
+    fn wait_for() -> Result<()> {
+        let session = testing_session()?;
+        // plain sh so that slow shell startup files don't get in the way
+        let window = Window::builder(&session)
+            .raw_command("sh".to_owned())
+            .build()?;
+        let pane = window.default_pane();
+        // the arithmetic makes sure that the typed in command itself doesn't match
+        pane.run_command("echo celeris_$((20 + 1))")?;
+        pane.wait_for("celeris_21", Duration::from_secs(5))?;
+
+        let start = Instant::now();
+        let _ = pane
+            .wait_for("celeris_never", Duration::from_millis(300))
+            .expect_err("should time out");
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        Ok(())
+    }
+
+    #[test]
+    fn escape_separators() {
+        assert_eq!(escape_separator("echo a; echo b"), "echo a; echo b");
+        assert_eq!(escape_separator("echo a;"), "echo a\\;");
+        assert_eq!(escape_separator(";"), "\\;");
+    }
+
+    #[test]
+    fn run_command_literal() -> Result<()> {
+        let mock = Rc::new(MockExecutor::default());
+        let pane = build_pane(
+            SessionTarget::new("$1")
+                .window_target("@1")
+                .pane_target("%1"),
+        );
+        with_executor(mock.clone(), || pane.run_command("cd /tmp; ls -l;"))?;
+        assert_eq!(
+            mock.commands()[1],
+            [
+                "send-keys",
+                "-t",
+                "$1:@1.%1",
+                "-l",
+                "--",
+                "cd /tmp; ls -l\\;",
+                ";",
+                "send-keys",
+                "-t",
+                "$1:@1.%1",
+                "Enter"
+            ]
+        );
+        Ok(())
+    }
+
+    fn pane_dead(pane: &Pane) -> Result<bool> {
+        let dead = pane
+            .target()
+            .targeted_command("display-message")?
+            .args(["-p", "#{pane_dead}"])
+            .execute()?;
+        Ok(dead.trim() == "1")
+    }
+
+    fn wait_dead(pane: &Pane) -> Result<()> {
+        let start = Instant::now();
+        while !pane_dead(pane)? {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "the pane should have stayed dead"
+            );
+            thread::sleep(Duration::from_millis(100));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn run_command_keep_alive() -> Result<()> {
+        let session = testing_session()?;
+        let window = Window::builder(&session)
+            .raw_command("sh".to_owned())
+            .build()?;
+        let pane = window.default_pane();
+        pane.run_command_keep_alive("exit")?;
+        wait_dead(&pane)
+    }
+
+    #[test]
+    fn remain_on_exit_respawn() -> Result<()> {
+        let session = testing_session()?;
+        let window = Window::builder(&session)
+            .raw_command("sh".to_owned())
+            .build()?;
+        let pane = window.default_pane();
+        pane.set_remain_on_exit(true)?;
+        pane.run_command("exit")?;
+        wait_dead(&pane)?;
+
+        pane.respawn(Some("sh"))?;
+        assert!(!pane_dead(&pane)?);
+        Ok(())
+    }
+
+    #[test]
+    fn run_command_separators() -> Result<()> {
+        let session = testing_session()?;
+        let window = Window::builder(&session)
+            .raw_command("sh".to_owned())
+            .build()?;
+        let pane = window.default_pane();
+        // neither `;` ends the tmux command, the typed in line itself has a quote in between
+        pane.run_command("echo 'celeris; a'   b;")?;
+        pane.wait_for("celeris; a b", Duration::from_secs(5))?;
+        Ok(())
+    }
+
     // Just checks for error. Testing this would be complicated
     #[test]
     fn run_command() -> Result<()> {
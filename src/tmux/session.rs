@@ -1,15 +1,11 @@
 use crate::tmux::RootOptions;
 #[allow(unused)]
 use crate::tmux::{
-    self, Root, SessionTarget, Target, TerminalState, TmuxExecuteExt, WindowTarget, tmux,
-    window::WindowCore,
-};
-use crate::utils;
-use color_eyre::eyre::ContextCompat;
-use color_eyre::{
-    Result,
-    eyre::{OptionExt, WrapErr, eyre},
+    self, Error, Result, Root, SessionTarget, Target, TerminalState, TmuxExecuteExt, WindowTarget,
+    path_to_str, tmux,
+    window::{Window, WindowCore},
 };
+use color_eyre::owo_colors::OwoColorize;
 use itertools::Itertools;
 use std::env;
 use std::io::Read;
@@ -21,6 +17,8 @@ use std::sync::{Arc, Mutex};
 pub struct SessionBuilder {
     root: Root,
     session_name: String,
+    default_window_name: Option<String>,
+    group: Option<String>,
 }
 
 impl SessionBuilder {
@@ -29,17 +27,35 @@ impl SessionBuilder {
         Self {
             root: Root::default(),
             session_name,
+            default_window_name: None,
+            group: None,
+        }
+    }
+
+    /// Names the window tmux creates along with the session and keeps it around as the first
+    /// window, available through [`Session::default_window`]. Otherwise it gets replaced by the
+    /// first window created
+    pub fn default_window_name(self, name: String) -> Self {
+        Self {
+            default_window_name: Some(name),
+            ..self
+        }
+    }
+
+    /// Puts the session in the group of the `target` session. Sessions in a group share the same
+    /// windows: windows created or killed in one of them show up in all of them, while each session
+    /// keeps its own current window, so clients attached to them can look at different windows.
+    /// There's no default window, the session starts with the windows of the group
+    pub fn group(self, target: String) -> Self {
+        Self {
+            group: Some(target),
+            ..self
         }
     }
 
     pub fn root(self, path: PathBuf) -> Result<Self> {
         Ok(Self {
-            root: Root::custom(path.clone()).wrap_err_with(|| {
-                format!(
-                    "session root for session: {} is invalid: {path:?}",
-                    &self.session_name
-                )
-            })?,
+            root: Root::custom(path)?,
             ..self
         })
     }
@@ -55,54 +71,109 @@ impl SessionBuilder {
             "-P",
             "-F",
             &format!(
-                "{}{}{}",
+                "{}{}{}{}{}",
                 "#{window_id}",
                 Self::OUTPUT_DELIM,
-                "#{session_id}"
+                "#{session_id}",
+                Self::OUTPUT_DELIM,
+                "#{pane_id}"
             ),
         ]);
 
         self.prepare_root(&mut command)?;
+        self.prepare_group(&mut command)?;
+        self.prepare_default_window_name(&mut command);
         Ok(command)
     }
 
+    fn prepare_group(&self, command: &mut Command) -> Result<()> {
+        let Some(group) = &self.group else {
+            return Ok(());
+        };
+        if self.default_window_name.is_some() {
+            return Err(Error::InvalidOption(format!(
+                "session: {} is grouped with: {group}, it shares its windows so it can't have a default window name",
+                self.session_name
+            )));
+        }
+        command.args(["-t", group]);
+        Ok(())
+    }
+
+    fn prepare_default_window_name(&self, command: &mut Command) {
+        if let Some(name) = &self.default_window_name {
+            command.args(["-n", name]);
+        }
+    }
+
     fn prepare_root(&self, command: &mut Command) -> Result<()> {
         if let RootOptions::Custom(root) = self.root.as_ref() {
-            command.args(["-c", &utils::path_to_string(root)?]);
+            command.args(["-c", path_to_str(root)?]);
         }
         Ok(())
     }
 
     pub fn build(&mut self) -> Result<Arc<Session>> {
         if SessionTarget::new(&self.session_name).target_exists()? {
-            return Err(eyre!(
-                "session with name: {}, already exists",
-                self.session_name
-            ));
+            return Err(Error::AlreadyExists(self.session_name.clone()));
         }
 
         let mut command = self.prepare()?;
         let output = command.execute()?;
-        let (default_window_id, session_id) = output
-            .trim()
-            .split_once(Self::OUTPUT_DELIM)
-            .ok_or_eyre(format!(
-                "failed to create session, couldn't parse session or window id: {}",
-                output
-            ))?;
+        let [default_window_id, session_id, default_pane_id] =
+            output.trim().splitn(3, Self::OUTPUT_DELIM).collect_vec()[..]
+        else {
+            return Err(Error::ParseError(format!(
+                "session, window or pane id of a new session: {output}"
+            )));
+        };
+        if self.group.is_some() {
+            // the windows come from the group, there's no default window to replace
+            return Session::from(session_id);
+        }
+
         let session_target = SessionTarget::new(session_id);
         let default_window_target = session_target.window_target(default_window_id);
-        Ok(Session::new(session_target, default_window_target))
+        let default_window = match self.default_window_name {
+            Some(_) => {
+                // same as with named windows, otherwise the name gets overwritten
+                default_window_target
+                    .targeted_command("set-window-option")?
+                    .args(["allow-rename", "off"])
+                    .execute()?;
+                let default_pane_target = default_window_target.pane_target(default_pane_id);
+                Some(Window::from_targets(
+                    default_window_target.clone(),
+                    default_pane_target,
+                ))
+            }
+            None => None,
+        };
+        Ok(Session::new(
+            session_target,
+            default_window_target,
+            default_window,
+        ))
     }
 }
 
 impl tmux::BuilderTransform for SessionBuilder {}
 
+#[derive(Debug)]
+struct WindowState {
+    // total amount of windows in the session including the default one, only windows created
+    // through this object are accounted for after construction
+    count: usize,
+    // whether the default window is still waiting to be replaced by the first created window
+    replace_default: bool,
+}
+
 #[derive(Debug)]
 pub struct Session {
-    window_count: Mutex<usize>,
+    windows: Mutex<WindowState>,
     target: SessionTarget,
     default_window_target: WindowTarget,
+    default_window: Option<Window>,
 }
 
 impl Session {
@@ -111,18 +182,26 @@ impl Session {
     }
 
     // Can't run this if in tmux session already
-    fn new(target: SessionTarget, default_window_target: WindowTarget) -> Arc<Self> {
+    fn new(
+        target: SessionTarget,
+        default_window_target: WindowTarget,
+        default_window: Option<Window>,
+    ) -> Arc<Self> {
         Arc::new(Self {
-            window_count: Mutex::new(0),
+            windows: Mutex::new(WindowState {
+                count: 1,
+                replace_default: default_window.is_none(),
+            }),
             target,
             default_window_target,
+            default_window,
         })
     }
 
     pub fn from(session_identifier: &str) -> Result<Arc<Session>> {
         let session_identifier = format!("{session_identifier}:");
         if !SessionTarget::new(&session_identifier).target_exists()? {
-            return Err(eyre!("session: {session_identifier}, doesn't exist"));
+            return Err(Error::TargetNotFound(session_identifier));
         }
 
         const DELIM: &str = "|";
@@ -142,20 +221,25 @@ impl Session {
         let [default_window_id, session_id, window_count] =
             output.trim().splitn(3, DELIM).collect_vec()[..]
         else {
-            return Err(eyre!(
+            return Err(Error::ParseError(format!(
                 "incorrect count of variables returned from display-message {output}"
-            ));
+            )));
         };
-        let window_count = window_count.parse::<usize>().wrap_err(
-            "failed to parse window_count while creating session object from existing session",
-        )?;
+        let window_count = window_count
+            .parse::<usize>()
+            .map_err(|_| Error::ParseError(format!("window count: {window_count}")))?;
 
         let target = SessionTarget::new(session_id);
         let default_window_target = target.window_target(default_window_id);
         Ok(Arc::new(Self {
-            window_count: window_count.into(),
+            // an existing session has no default window waiting to be replaced
+            windows: Mutex::new(WindowState {
+                count: window_count,
+                replace_default: false,
+            }),
             target,
             default_window_target,
+            default_window: None,
         }))
     }
 
@@ -164,22 +248,23 @@ impl Session {
         Ok(match env::var("TMUX") {
             Ok(_) => TerminalState::InTmux,
             Err(env::VarError::NotPresent) => TerminalState::Normal,
-            Err(err) => return Err(err).wrap_err("failed to check for active tmux session"),
+            Err(_) => return Err(Error::InvalidUnicode("$TMUX".to_owned())),
         })
     }
 
+    // Called on every status bar redraw through `celeris current`, so on the happy path it's a
+    // single tmux command. Whether the server is running is only checked if that one fails
     pub fn active_name() -> Result<Option<String>> {
-        if !tmux::server_running()? {
-            return Ok(None);
-        }
-
         if let TerminalState::Normal = Self::terminal_state()? {
             return Ok(None);
         }
 
         let output = tmux()?
             .args(["display-message", "-p", "#{client_session}"])
-            .execute()?;
+            .execute();
+        let Some(output) = Self::unless_server_gone(output)? else {
+            return Ok(None);
+        };
         if output.trim().is_empty() {
             Ok(None)
         } else {
@@ -187,59 +272,188 @@ impl Session {
         }
     }
 
-    pub fn list_sessions() -> Result<Vec<String>> {
-        if !tmux::server_running()? {
-            return Ok(Vec::new());
+    /// Current path of the active pane of the client, which can differ from the working directory
+    /// of celeris e.g. when it's run from within another program. None outside of tmux
+    pub fn active_pane_path() -> Result<Option<PathBuf>> {
+        if let TerminalState::Normal = Self::terminal_state()? {
+            return Ok(None);
         }
+
+        let output = tmux()?
+            .args(["display-message", "-p", "#{pane_current_path}"])
+            .execute();
+        let Some(output) = Self::unless_server_gone(output)? else {
+            return Ok(None);
+        };
+        match output.trim() {
+            "" => Ok(None),
+            path => Ok(Some(PathBuf::from(path))),
+        }
+    }
+
+    pub fn list_sessions() -> Result<Vec<String>> {
+        // whether the server is running is only checked if this fails, to spare a tmux process
         let output = tmux()?
             .args(["list-sessions", "-F", "#{session_name}"])
-            .execute()?;
+            .execute();
+        let Some(output) = Self::unless_server_gone(output)? else {
+            return Ok(Vec::new());
+        };
         Ok(output.trim().lines().map(ToOwned::to_owned).collect())
     }
 
-    fn spawn_attach(&self, attached: TerminalState) -> Result<(Command, Child)> {
-        let mut command = match attached {
-            TerminalState::InTmux => self.target().targeted_command("switch-client")?,
-            TerminalState::Normal => self.target().targeted_command("attach-session")?,
+    /// Every running session along with whether it's the one the current client is attached to.
+    /// `#{session_attached}` counts clients in any terminal, so the active session is asked for
+    /// separately, but within the same tmux invocation
+    pub fn session_states() -> Result<Vec<(String, bool)>> {
+        let terminal_state = Self::terminal_state()?;
+        let mut command = tmux()?;
+        command.args(["list-sessions", "-F", "#{session_name}"]);
+        if let TerminalState::InTmux = terminal_state {
+            command.args([";", "display-message", "-p", "#{client_session}"]);
+        }
+
+        let Some(output) = Self::unless_server_gone(command.execute())? else {
+            return Ok(Vec::new());
+        };
+        let mut lines = output.lines().map(ToOwned::to_owned).collect_vec();
+        let active_name = match terminal_state {
+            TerminalState::InTmux => lines.pop(),
+            TerminalState::Normal => None,
+        };
+        Ok(lines
+            .into_iter()
+            .map(|name| {
+                let attached = active_name.as_ref() == Some(&name);
+                (name, attached)
+            })
+            .collect())
+    }
+
+    // The server exits along with its last session, so it can disappear between checking if it's
+    // running and querying it. That's the same as it not running at all
+    fn unless_server_gone(output: Result<String>) -> Result<Option<String>> {
+        match output {
+            Ok(output) => Ok(Some(output)),
+            Err(Error::ServerNotRunning) => Ok(None),
+            Err(_) if !tmux::server_running()? => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // The socket of the server the current client is attached to, the first field of $TMUX
+    fn client_socket() -> Result<Option<String>> {
+        match env::var("TMUX") {
+            Ok(tmux) => Ok(tmux.split(',').next().map(str::to_owned)),
+            Err(env::VarError::NotPresent) => Ok(None),
+            Err(_) => Err(Error::InvalidUnicode("$TMUX".to_owned())),
+        }
+    }
+
+    fn same_socket(a: &str, b: &str) -> bool {
+        match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        }
+    }
+
+    // $TMUX is also set where there's no client to switch, e.g. in a control mode context or with
+    // the $TMUX of a client that's gone. attach-session is the only way to attach then. The same
+    // goes for a $TMUX of another server than the one celeris talks to, switch-client would act on
+    // one of that server's clients instead, so a nested client is attached
+    fn attach_state(client_socket: Option<&str>) -> Result<TerminalState> {
+        let Some(client_socket) = client_socket else {
+            return Ok(TerminalState::Normal);
+        };
+
+        let Ok(output) = tmux()?
+            .args(["display-message", "-p", "#{socket_path}|#{client_tty}"])
+            .execute()
+        else {
+            return Ok(TerminalState::Normal);
         };
+        let Some((server_socket, client_tty)) =
+            output.trim().split_once(SessionBuilder::OUTPUT_DELIM)
+        else {
+            return Ok(TerminalState::Normal);
+        };
+
+        if !Self::same_socket(client_socket, server_socket) {
+            eprintln!(
+                "{}: the current tmux client is attached to {client_socket}, not {server_socket}, attaching a nested client",
+                "warning".yellow().bold()
+            );
+            return Ok(TerminalState::Normal);
+        }
+        Ok(match client_tty.is_empty() {
+            false => TerminalState::InTmux,
+            true => TerminalState::Normal,
+        })
+    }
+
+    fn attach_command(&self, attached: TerminalState, client: Option<&str>) -> Result<Command> {
+        Ok(match attached {
+            TerminalState::InTmux => {
+                let mut command = self.target().targeted_command("switch-client")?;
+                if let Some(client) = client {
+                    command.args(["-c", client]);
+                }
+                command
+            }
+            TerminalState::Normal => {
+                let mut command = self.target().targeted_command("attach-session")?;
+                // otherwise tmux refuses to nest
+                command.env_remove("TMUX");
+                command
+            }
+        })
+    }
 
-        let child = command
-            .stderr(Stdio::piped())
-            .spawn()
-            .wrap_err("failed to execute attach session command")?;
+    fn spawn_attach(
+        &self,
+        attached: TerminalState,
+        client: Option<&str>,
+    ) -> Result<(Command, Child)> {
+        let mut command = self.attach_command(attached, client)?;
+
+        let child = command.stderr(Stdio::piped()).spawn()?;
 
         Ok((command, child))
     }
 
     fn wait_attach(&self, command: Command, mut handle: Child) -> Result<()> {
-        let status = handle
-            .wait()
-            .wrap_err("failed to wait for attach session command, couldn't get status")?;
+        let status = handle.wait()?;
 
         if !status.success() {
             let mut error = String::new();
-            handle
-                .stderr
-                .take()
-                .wrap_err("stderr of tmux not available")
-                .wrap_err(format!(
-                    "failed to retrieve error from failing tmux: {:?}",
-                    command
-                ))?
-                .read_to_string(&mut error)
-                .wrap_err(format!(
-                    "failed to retrieve error from failing tmux: {:?}",
-                    command
-                ))?;
-            return Err(eyre!("tmux: {:?}, failed with: {error}", command)
-                .wrap_err("failed to attach session"));
+            if let Some(mut stderr) = handle.stderr.take() {
+                stderr.read_to_string(&mut error)?;
+            }
+            let action = match command.get_args().any(|arg| arg == "switch-client") {
+                true => "switch-client",
+                false => "attach-session",
+            };
+            return Err(Error::AttachFailed {
+                session: self.target().get().to_owned(),
+                message: format!("{action} failed with: {}", error.trim()),
+            });
         }
 
         Ok(())
     }
 
     pub fn attach(&self) -> Result<()> {
-        let (command, handle) = self.spawn_attach(Self::terminal_state()?)?;
+        self.attach_with(None)
+    }
+
+    /// Switches `client`, e.g. its tty, to the session instead of the current one. A given client
+    /// can be switched from anywhere, also outside of tmux
+    pub fn attach_with(&self, client: Option<&str>) -> Result<()> {
+        let attached = match client {
+            Some(_) => TerminalState::InTmux,
+            None => Self::attach_state(Self::client_socket()?.as_deref())?,
+        };
+        let (command, handle) = self.spawn_attach(attached, client)?;
         self.wait_attach(command, handle)?;
         Ok(())
     }
@@ -247,15 +461,34 @@ impl Session {
     pub fn target(&self) -> &SessionTarget {
         &self.target
     }
+
+    /// The window created along with the session, only available if it was kept with
+    /// [`SessionBuilder::default_window_name`]
+    pub fn default_window(&self) -> Option<&Window> {
+        self.default_window.as_ref()
+    }
+
+    /// Total amount of windows in the session, including the default one until it's replaced
+    pub fn window_count(&self) -> usize {
+        self.windows.lock().unwrap().count
+    }
 }
 
-pub fn register_window(session: &Session, window: &WindowCore) -> Result<()> {
-    let mut count = session.window_count.lock().unwrap();
-    if *count == 0 {
-        window.move_kill(&session.default_window_target)?;
+// The lock is held while the window is created, otherwise concurrently created windows could all
+// see the default window as not replaced yet or none of them could replace it
+pub fn register_window(
+    session: &Session,
+    create: impl FnOnce() -> Result<WindowCore>,
+) -> Result<WindowCore> {
+    let mut windows = session.windows.lock().unwrap();
+    let window = create()?;
+    if windows.replace_default {
+        window.replace(&session.default_window_target)?;
+        windows.replace_default = false;
+    } else {
+        windows.count += 1;
     }
-    *count += 1;
-    Ok(())
+    Ok(window)
 }
 
 #[cfg(test)]
@@ -264,8 +497,12 @@ mod tests {
 
     use super::*;
     use crate::tmux::session::{Session, TmuxExecuteExt};
-    use crate::tmux::{Window, tests::*};
+    use crate::tmux::{Window, tests::*, with_executor};
+    use crate::utils;
     use color_eyre::Result;
+    use color_eyre::eyre::{OptionExt, eyre};
+    use std::rc::Rc;
+    use std::thread;
 
     #[test]
     fn from() -> Result<()> {
@@ -275,6 +512,7 @@ mod tests {
             .build()?;
 
         let session_from = Session::from(TESTING_SESSION)?;
+        assert_eq!(session_from.window_count(), session.window_count());
         let output = session_from
             .target()
             .targeted_command("display-message")?
@@ -286,6 +524,72 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn window_count() -> Result<()> {
+        let session = testing_session()?;
+        assert_eq!(session.window_count(), 1);
+        let _window1 = Window::builder(&session).build()?;
+        assert_eq!(session.window_count(), 1);
+        let _window2 = Window::builder(&session).build()?;
+        assert_eq!(session.window_count(), 2);
+
+        // dropping it would kill the session
+        let session_from = Session::from(TESTING_SESSION)?;
+        assert_eq!(session_from.window_count(), 2);
+        let _window3 = Window::builder(&session_from).build()?;
+        assert_eq!(session_from.window_count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_windows() -> Result<()> {
+        const COUNT: usize = 8;
+        let session = testing_session()?;
+        let handles = (0..COUNT)
+            .map(|_| {
+                let session = Arc::clone(&session);
+                thread::spawn(move || Window::builder(&session).build().map(|_| ()))
+            })
+            .collect_vec();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("window builder thread panicked"))?;
+
+        let output = session
+            .target()
+            .targeted_command("list-windows")?
+            .execute()?;
+        assert_eq!(output.lines().count(), COUNT);
+        assert_eq!(session.window_count(), COUNT);
+        Ok(())
+    }
+
+    // The default window is replaced by the first window of a layout, with renumber-windows on
+    // killing it renumbers the rest right away
+    #[test]
+    fn renumber_windows() -> Result<()> {
+        let session = testing_session()?;
+        session
+            .target()
+            .targeted_command("set-option")?
+            .args(["renumber-windows", "on"])
+            .execute()?;
+
+        let first = Window::builder(&session).name("first".to_owned()).build()?;
+        let windows = session
+            .target()
+            .targeted_command("list-windows")?
+            .args(["-F", "#{session_id}:#{window_id}"])
+            .execute()?;
+        assert_eq!(windows.lines().collect_vec(), [first.target().get()]);
+
+        Window::builder(&session)
+            .name("second".to_owned())
+            .build()?;
+        assert_eq!(session.window_count(), 2);
+        Ok(())
+    }
+
     #[test]
     fn target_exits() -> Result<()> {
         let session = testing_session()?;
@@ -360,6 +664,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn session_states() -> Result<()> {
+        let session_name = "__celeris_testing_states";
+        let _session = SessionBuilder::new(session_name.to_owned()).build()?;
+        let states = Session::session_states()?;
+        assert!(states.iter().any(|(name, _)| name == session_name));
+        assert_eq!(
+            states
+                .into_iter()
+                .find(|(_, attached)| *attached)
+                .map(|(name, _)| name),
+            Session::active_name()?
+        );
+        Ok(())
+    }
+
     #[test]
     fn new_session() -> Result<()> {
         let session = testing_session()?;
@@ -367,6 +687,65 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn default_window_replaced() -> Result<()> {
+        let session = testing_session()?;
+        let default_window = &session.default_window_target;
+        default_window
+            .targeted_command("rename-window")?
+            .arg("renamed")
+            .execute()?;
+        default_window
+            .targeted_command("send-keys")?
+            .args(["'cat'", "Enter"])
+            .execute()?;
+        let default_index = default_window
+            .targeted_command("display-message")?
+            .args(["-p", "#{window_index}"])
+            .execute()?;
+
+        let window = Window::builder(&session).name("first".to_owned()).build()?;
+        let output = session
+            .target()
+            .targeted_command("list-windows")?
+            .args([
+                "-F",
+                "#{session_id}:#{window_id}|#{window_index}|#{window_name}",
+            ])
+            .execute()?;
+        assert_eq!(
+            output.trim(),
+            format!("{}|{}|first", window.target().get(), default_index.trim())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn default_window_kept() -> Result<()> {
+        let session = SessionBuilder::new(TESTING_SESSION.to_owned())
+            .default_window_name("first".to_owned())
+            .build()?;
+        let default_window = session
+            .default_window()
+            .ok_or_eyre("default window should be kept")?;
+        let _window = Window::builder(&session).build()?;
+
+        assert!(default_window.target().target_exists()?);
+        assert!(default_window.default_pane().target().target_exists()?);
+        let output = session
+            .target()
+            .targeted_command("list-windows")?
+            .args(["-F", "#{window_name}"])
+            .execute()?;
+        assert_eq!(output.lines().count(), 2);
+        assert_eq!(output.lines().next(), Some("first"));
+        drop(session);
+
+        let session = testing_session()?;
+        assert!(session.default_window().is_none());
+        Ok(())
+    }
+
     #[test]
     fn new_session_custom_root() -> Result<()> {
         let session = SessionBuilder::new(TESTING_SESSION.to_owned())
@@ -381,16 +760,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn root_not_a_directory() -> Result<()> {
+        let file = env::temp_dir().join("__celeris_testing_session_root");
+        std::fs::write(&file, "")?;
+        let err = SessionBuilder::new(TESTING_SESSION.to_owned())
+            .root(file)
+            .expect_err("a file isn't a valid session root");
+        assert!(matches!(err, tmux::Error::RootNotADirectory(_)));
+        Ok(())
+    }
+
     fn attach_test(attached: TerminalState) -> Result<()> {
         let session = testing_session()?;
-        let (command, handle) = session.spawn_attach(attached.clone())?;
+        let (command, handle) = session.spawn_attach(attached.clone(), None)?;
         let output = session
             .target()
             .targeted_command("display-message")?
             .args(["-p", "#{session_attached}"])
             .execute()?;
         if output.trim() != "1" {
-            return Err(session.wait_attach(command, handle).unwrap_err());
+            return Err(session.wait_attach(command, handle).unwrap_err().into());
         }
         match attached {
             TerminalState::Normal => session.detach_clients()?,
@@ -445,4 +835,219 @@ mod tests {
         attach_test(TerminalState::Normal)?;
         Ok(())
     }
+
+    #[test]
+    fn prepare() -> Result<()> {
+        let builder = Session::builder("name".to_owned());
+        assert_eq!(
+            command_args(&builder.prepare()?),
+            [
+                "new-session",
+                "-d",
+                "-s",
+                "name",
+                "-P",
+                "-F",
+                "#{window_id}|#{session_id}|#{pane_id}"
+            ]
+        );
+
+        let root = env::temp_dir();
+        let builder = Session::builder("name".to_owned())
+            .root(root.clone())?
+            .default_window_name("main".to_owned());
+        assert_eq!(
+            command_args(&builder.prepare()?)[7..],
+            ["-c", &utils::path_to_string(&root)?, "-n", "main"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn prepare_group() -> Result<()> {
+        let builder = Session::builder("name".to_owned()).group("base".to_owned());
+        assert_eq!(command_args(&builder.prepare()?)[7..], ["-t", "base"]);
+
+        let builder = builder.default_window_name("main".to_owned());
+        assert!(builder.prepare().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn group() -> Result<()> {
+        let base = testing_session()?;
+        let _window = Window::builder(&base).build()?;
+        let grouped = Session::builder("__celeris_testing_grouped".to_owned())
+            .group(TESTING_SESSION.to_owned())
+            .build()?;
+        assert_eq!(grouped.window_count(), 1);
+
+        // windows created in either one show up in both
+        let _window = Window::builder(&grouped).build()?;
+        let _window = Window::builder(&base).build()?;
+        for session in [&base, &grouped] {
+            let windows = session
+                .target()
+                .targeted_command("display-message")?
+                .args(["-p", "#{session_windows}"])
+                .execute()?;
+            assert_eq!(windows.trim(), "3");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn attach_state() -> Result<()> {
+        let socket = "/tmp/tmux-1000/default";
+        let mock = Rc::new(MockExecutor::default());
+        mock.respond(&format!("{socket}|/dev/pts/3\n"));
+        // e.g. a control mode client has no tty
+        mock.respond(&format!("{socket}|\n"));
+        mock.fail("no current client");
+        let states = with_executor(mock.clone(), || -> Result<_> {
+            Ok([
+                Session::attach_state(Some(socket))?,
+                Session::attach_state(Some(socket))?,
+                Session::attach_state(Some(socket))?,
+                Session::attach_state(None)?,
+            ])
+        })?;
+        assert!(matches!(
+            states,
+            [
+                TerminalState::InTmux,
+                TerminalState::Normal,
+                TerminalState::Normal,
+                TerminalState::Normal
+            ]
+        ));
+        // outside of tmux there's nothing to ask
+        assert_eq!(mock.commands().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn active_pane_path() -> Result<()> {
+        let tmux_env = env::var_os("TMUX");
+        unsafe {
+            env::set_var("TMUX", "/tmp/tmux-1000/default,1,0");
+        }
+        let mock = Rc::new(MockExecutor::default());
+        mock.respond("/home/user/src\n");
+        mock.respond("\n");
+        let paths = with_executor(mock.clone(), || -> Result<_> {
+            Ok([Session::active_pane_path()?, Session::active_pane_path()?])
+        });
+        unsafe {
+            match tmux_env {
+                Some(tmux) => env::set_var("TMUX", tmux),
+                None => env::remove_var("TMUX"),
+            }
+        }
+        assert_eq!(paths?, [Some(PathBuf::from("/home/user/src")), None]);
+        assert_eq!(
+            mock.commands()[0],
+            ["display-message", "-p", "#{pane_current_path}"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn attach_state_socket_mismatch() -> Result<()> {
+        let mock = Rc::new(MockExecutor::default());
+        mock.respond("/tmp/tmux-1000/celeris|/dev/pts/3\n");
+        let state = with_executor(mock.clone(), || {
+            Session::attach_state(Some("/tmp/tmux-1000/default"))
+        })?;
+        // switch-client would switch a client of the other server
+        assert!(matches!(state, TerminalState::Normal));
+        Ok(())
+    }
+
+    #[test]
+    fn attach_command() -> Result<()> {
+        with_mocked_session(|session| -> Result<()> {
+            let command = session.attach_command(TerminalState::InTmux, None)?;
+            assert_eq!(command_args(&command), ["switch-client", "-t", "$1"]);
+            let command = session.attach_command(TerminalState::InTmux, Some("/dev/pts/2"))?;
+            assert_eq!(
+                command_args(&command),
+                ["switch-client", "-t", "$1", "-c", "/dev/pts/2"]
+            );
+            let command = session.attach_command(TerminalState::Normal, Some("/dev/pts/2"))?;
+            assert_eq!(command_args(&command), ["attach-session", "-t", "$1"]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn attach_with_client() {
+        // the given client is switched even from outside of tmux, the mocked session doesn't
+        // exist on the server so the switch itself fails
+        let result = with_mocked_session(|session| session.attach_with(Some("/dev/pts/999")));
+        assert!(matches!(
+            result,
+            Err(tmux::Error::AttachFailed { message, .. }) if message.starts_with("switch-client")
+        ));
+    }
+
+    #[test]
+    fn build_mocked() -> Result<()> {
+        let mock = Rc::new(MockExecutor::default());
+        mock.fail("can't find session");
+        mock.respond("@3|$2|%5\n");
+        with_executor(mock.clone(), || -> Result<()> {
+            let session = Session::builder("mocked".to_owned())
+                .default_window_name("main".to_owned())
+                .build()?;
+            assert_eq!(session.target().get(), "$2");
+            let default_window = session
+                .default_window()
+                .ok_or_eyre("default window missing")?;
+            assert_eq!(default_window.target().get(), "$2:@3");
+
+            let commands = mock.commands();
+            assert_eq!(commands.len(), 4);
+            assert_eq!(commands[0], ["has-session", "-t", "mocked"]);
+            assert_eq!(
+                commands[1],
+                [
+                    "new-session",
+                    "-d",
+                    "-s",
+                    "mocked",
+                    "-P",
+                    "-F",
+                    "#{window_id}|#{session_id}|#{pane_id}",
+                    "-n",
+                    "main"
+                ]
+            );
+            assert_eq!(commands[2], ["has-session", "-t", "$2:@3"]);
+            assert_eq!(
+                commands[3],
+                ["set-window-option", "-t", "$2:@3", "allow-rename", "off"]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn build_mocked_malformed() {
+        let mock = Rc::new(MockExecutor::default());
+        mock.fail("can't find session");
+        mock.respond("@3");
+        let result = with_executor(mock, || Session::builder("mocked".to_owned()).build());
+        assert!(matches!(result, Err(tmux::Error::ParseError(_))));
+    }
+
+    #[test]
+    fn build_mocked_exists() {
+        let mock = Rc::new(MockExecutor::default());
+        let result = with_executor(mock.clone(), || {
+            Session::builder("mocked".to_owned()).build()
+        });
+        assert!(matches!(result, Err(tmux::Error::AlreadyExists(name)) if name == "mocked"));
+        assert_eq!(mock.commands().len(), 1);
+    }
 }
@@ -2,10 +2,82 @@ use super::*;
 use crate::tmux::Target;
 use crate::tmux::session::{Session, SessionBuilder};
 use color_eyre::Result;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::process::Command;
+use std::rc::Rc;
 use std::sync::Arc;
 
 pub const TESTING_SESSION: &str = "__celeris_testing";
 
+/// Answers tmux commands without a server. The queued responses are handed out in order, once
+/// they run out commands succeed with empty output
+#[derive(Default)]
+pub struct MockExecutor {
+    responses: RefCell<VecDeque<Result<String, String>>>,
+    commands: RefCell<Vec<Vec<String>>>,
+}
+
+impl MockExecutor {
+    pub fn respond(&self, output: &str) {
+        self.responses.borrow_mut().push_back(Ok(output.to_owned()));
+    }
+
+    pub fn fail(&self, error: &str) {
+        self.responses.borrow_mut().push_back(Err(error.to_owned()));
+    }
+
+    /// Arguments of the commands run so far, without the program and the socket
+    pub fn commands(&self) -> Vec<Vec<String>> {
+        self.commands.borrow().clone()
+    }
+
+    fn next(&self, command: &Command) -> Result<String, String> {
+        self.commands.borrow_mut().push(command_args(command));
+        self.responses
+            .borrow_mut()
+            .pop_front()
+            .unwrap_or(Ok(String::new()))
+    }
+}
+
+impl TmuxExecutor for MockExecutor {
+    fn execute(&self, command: &mut Command) -> super::Result<String> {
+        self.next(command).map_err(|stderr| Error::CommandFailed {
+            args: command_args(command),
+            stderr,
+        })
+    }
+
+    fn succeeds(&self, command: &mut Command) -> super::Result<bool> {
+        Ok(self.next(command).is_ok())
+    }
+}
+
+/// Arguments of a tmux command without the program and the socket
+pub fn command_args(command: &Command) -> Vec<String> {
+    let socket_args = tmux().map_or(0, |tmux| tmux.get_args().len());
+    command
+        .get_args()
+        .skip(socket_args)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Runs `f` with a session that only exists in a [`MockExecutor`], which stays installed until the
+/// session is dropped so that the cleanup doesn't reach a real server
+pub fn with_mocked_session<T>(f: impl FnOnce(&Arc<Session>) -> T) -> T {
+    let mock = Rc::new(MockExecutor::default());
+    mock.fail("can't find session");
+    mock.respond("@1|$1|%1");
+    with_executor(mock, || {
+        let session = Session::builder("mocked".to_owned())
+            .build()
+            .expect("mocked session creation can't fail");
+        f(&session)
+    })
+}
+
 impl Session {
     pub fn kill(&self) -> Result<()> {
         self.target().targeted_command("kill-session")?.execute()?;
@@ -143,6 +215,7 @@ fn tmux_test() -> Result<()> {
         format!("{command:?}"),
         format!("{:?}", Command::new("tmux").args(["-L", socket_name])),
     );
+    assert!(custom_socket());
 
     let socket_path = env::temp_dir().join(socket_name);
     unsafe {
@@ -161,5 +234,74 @@ fn tmux_test() -> Result<()> {
     unsafe {
         env::remove_var("CELERIS_TMUX_SOCKET_PATH");
     }
+    assert!(!custom_socket());
     Ok(())
 }
+
+#[test]
+fn kill_server_t() -> Result<()> {
+    let mock = Rc::new(MockExecutor::default());
+    with_executor(mock.clone(), kill_server)?;
+    assert_eq!(mock.commands()[1], ["kill-server"]);
+
+    // not running
+    let mock = Rc::new(MockExecutor::default());
+    mock.fail("no server running");
+    with_executor(mock.clone(), kill_server)?;
+    assert_eq!(mock.commands().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn root_custom() -> Result<()> {
+    let dir = env::temp_dir();
+    assert_eq!(
+        Root::custom(dir.clone())?.as_ref(),
+        &RootOptions::Custom(dir.clone())
+    );
+
+    let file = dir.join("__celeris_testing_root_file");
+    std::fs::write(&file, "")?;
+    let err = Root::custom(file.clone()).expect_err("a file isn't a valid root");
+    assert!(matches!(err, Error::RootNotADirectory(path) if path == file));
+    assert!(matches!(
+        Root::custom(dir.join("__celeris_missing_root")),
+        Err(Error::RootNotFound(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn command_failed() {
+    let mock = Rc::new(MockExecutor::default());
+    mock.fail("unknown option");
+    let result = with_executor(mock, || tmux()?.args(["list-sessions", "-X"]).execute());
+    let Err(Error::CommandFailed { args, stderr }) = result else {
+        panic!("expected CommandFailed, got: {result:?}");
+    };
+    assert_eq!(args, ["list-sessions", "-X"]);
+    assert_eq!(stderr, "unknown option");
+}
+
+#[test]
+fn server_not_running() {
+    assert!(is_server_gone(
+        "no server running on /tmp/tmux-1000/default\n"
+    ));
+    assert!(is_server_gone(
+        "error connecting to /tmp/tmux-1000/default (No such file or directory)\n"
+    ));
+    assert!(!is_server_gone("can't find session: missing\n"));
+}
+
+#[test]
+fn target_not_found() {
+    let mock = Rc::new(MockExecutor::default());
+    mock.fail("can't find session");
+    let result = with_executor(mock.clone(), || {
+        SessionTarget::new("missing").targeted_command("kill-session")
+    });
+    assert!(matches!(result, Err(Error::TargetNotFound(target)) if target == "missing"));
+    // the command itself isn't run
+    assert_eq!(mock.commands().len(), 1);
+}
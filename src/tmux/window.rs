@@ -1,18 +1,26 @@
 use crate::tmux::{
-    self, PaneTarget, Root, RootOptions, Target, TmuxExecuteExt, WindowTarget,
+    self, Error, PaneTarget, Result, Root, RootOptions, Target, TmuxExecuteExt, WindowTarget,
     pane::{self, Direction, Pane},
+    path_to_str,
     session::{self, Session},
 };
-use crate::utils;
-use color_eyre::{Result, eyre::OptionExt};
 use std::path::PathBuf;
+use std::process::Command;
 use std::sync::Arc;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+    Before,
+    After,
+    Index(u32),
+}
+
 #[derive(Debug, PartialEq, Eq)]
 struct WindowOptions {
     name: Option<String>,
     shell_command: Option<String>,
     root: Root,
+    position: Option<Position>,
 }
 
 #[derive(Debug)]
@@ -27,6 +35,7 @@ impl WindowBuilder {
             name: None,
             shell_command: None,
             root: Root::default(),
+            position: None,
         };
 
         Self { opts, session }
@@ -40,7 +49,9 @@ impl WindowBuilder {
         Self { opts, ..self }
     }
 
+    /// Working directory of the window, a relative path is relative to the root of the session
     pub fn root(self, path: PathBuf) -> Result<Self> {
+        let path = tmux::resolve_root(self.session.target(), path)?;
         let opts = WindowOptions {
             root: Root::custom(path)?,
             ..self.opts
@@ -56,8 +67,34 @@ impl WindowBuilder {
         Self { opts, ..self }
     }
 
+    /// Inserts the window before the current window of the session. Note that tmux may still
+    /// renumber windows afterwards e.g. with renumber-windows on
+    pub fn before(self) -> Self {
+        self.position(Position::Before)
+    }
+
+    /// Inserts the window after the current window of the session
+    pub fn after(self) -> Self {
+        self.position(Position::After)
+    }
+
+    /// Creates the window at the given index, fails if it's already taken. The first window of a
+    /// session takes over the place of the default one regardless of this
+    pub fn index(self, index: u32) -> Self {
+        self.position(Position::Index(index))
+    }
+
+    fn position(self, position: Position) -> Self {
+        let opts = WindowOptions {
+            position: Some(position),
+            ..self.opts
+        };
+        Self { opts, ..self }
+    }
+
     fn prepare_options(&self) -> Result<Vec<String>> {
         let mut options: Vec<String> = Vec::new();
+        self.prepare_position(&mut options);
         self.prepare_name(&mut options);
         self.prepare_root(&mut options)?;
         self.prepare_raw_command(&mut options);
@@ -73,6 +110,24 @@ impl WindowBuilder {
         options.extend(["-n".to_owned(), name.to_owned()]);
     }
 
+    fn prepare_position(&self, options: &mut Vec<String>) {
+        match self.opts.position {
+            Some(Position::Before) => options.push("-b".to_owned()),
+            Some(Position::After) => options.push("-a".to_owned()),
+            // handled by new_window_command since it's part of the target
+            Some(Position::Index(_)) | None => (),
+        }
+    }
+
+    fn new_window_command(&self) -> Result<Command> {
+        let mut command = self.session.target().targeted_command("new-window")?;
+        if let Some(Position::Index(index)) = self.opts.position {
+            // the last target takes precedence
+            command.args(["-t", &format!("{}:{index}", self.session.target().get())]);
+        }
+        Ok(command)
+    }
+
     fn prepare_raw_command(&self, options: &mut Vec<String>) {
         let Some(command) = &self.opts.shell_command else {
             return;
@@ -82,7 +137,7 @@ impl WindowBuilder {
 
     fn prepare_root(&self, options: &mut Vec<String>) -> Result<()> {
         let root = match self.opts.root.as_ref() {
-            RootOptions::Custom(path) => utils::path_to_string(path)?,
+            RootOptions::Custom(path) => path_to_str(path)?.to_owned(),
             RootOptions::Default => "#{pane_current_path}".to_owned(), // should inherit context
                                                                        // from our session not env
         };
@@ -93,9 +148,7 @@ impl WindowBuilder {
     fn create_window(&self) -> Result<WindowCore> {
         const DELIM: &str = "|";
         let output = self
-            .session
-            .target()
-            .targeted_command("new-window")?
+            .new_window_command()?
             .args([
                 "-P",
                 "-F",
@@ -103,10 +156,9 @@ impl WindowBuilder {
             ])
             .args(self.prepare_options()?)
             .execute()?;
-        let (default_pane_id, window_id) = output.trim().split_once(DELIM).ok_or_eyre(format!(
-            "failed to create session, couldn't parse session or window id: {}",
-            output
-        ))?;
+        let (default_pane_id, window_id) = output.trim().split_once(DELIM).ok_or_else(|| {
+            Error::ParseError(format!("pane or window id of a new window: {output}"))
+        })?;
 
         let target = self.session.target().window_target(window_id);
         let default_pane_target = target.pane_target(default_pane_id);
@@ -114,8 +166,7 @@ impl WindowBuilder {
     }
 
     pub fn build(self) -> Result<Window> {
-        let window_core = self.create_window()?;
-        session::register_window(&self.session, &window_core)?;
+        let window_core = session::register_window(&self.session, || self.create_window())?;
 
         if let Some(_) = self.opts.name {
             window_core.set_option("allow-rename", "off")?;
@@ -169,14 +220,15 @@ impl WindowCore {
         Ok(())
     }
 
-    // Only for the purpose of killing the default window
-    pub fn move_kill(&self, other: &WindowTarget) -> Result<()> {
-        // use a proper source target
-        // wtf
-        self.target()
-            .targeted_command("move-window")?
-            .args(["-s", self.target.get(), "-t", other.get(), "-k"])
+    // Only for the purpose of getting rid of the default window. Swaps places with it before
+    // killing it, so that the first window of a session stays at base-index. Moving the window
+    // into the freed index afterwards fails with renumber-windows on, the kill already did that
+    pub fn replace(&self, default_window: &WindowTarget) -> Result<()> {
+        default_window
+            .targeted_command("swap-window")?
+            .args(["-d", "-s", self.target.get()])
             .execute()?;
+        default_window.targeted_command("kill-window")?.execute()?;
         Ok(())
     }
 
@@ -202,6 +254,10 @@ impl Window {
         }
     }
 
+    pub(super) fn from_targets(target: WindowTarget, default_pane_target: PaneTarget) -> Self {
+        Self::new(WindowCore::new(target, default_pane_target))
+    }
+
     pub fn builder(session: &Arc<Session>) -> WindowBuilder {
         WindowBuilder::new(Arc::clone(session))
     }
@@ -227,10 +283,44 @@ impl Window {
 mod tests {
     use super::*;
     use crate::tmux::pane::Direction;
+    use crate::utils;
+    use color_eyre::Result;
     use color_eyre::eyre::OptionExt;
-    use std::{env, thread, time::Duration};
+    use std::{env, fs, thread, time::Duration};
     use tmux::tests::*;
 
+    #[test]
+    fn prepare_options() -> Result<()> {
+        with_mocked_session(|session| -> Result<()> {
+            assert_eq!(
+                Window::builder(session).prepare_options()?,
+                ["-c", "#{pane_current_path}"]
+            );
+
+            let root = env::temp_dir();
+            let root_string = utils::path_to_string(&root)?;
+            let builder = Window::builder(session)
+                .name("editor".to_owned())
+                .root(root)?
+                .raw_command("nvim".to_owned())
+                .after();
+            assert_eq!(
+                builder.prepare_options()?,
+                ["-a", "-n", "editor", "-c", &root_string, "nvim"]
+            );
+
+            let builder = Window::builder(session).before();
+            assert_eq!(builder.prepare_options()?[0], "-b");
+
+            // the index is part of the target rather than an option
+            let builder = Window::builder(session).index(3);
+            assert_eq!(builder.prepare_options()?, ["-c", "#{pane_current_path}"]);
+            let args = command_args(&builder.new_window_command()?);
+            assert_eq!(args[args.len() - 2..], ["-t", "$1:3"]);
+            Ok(())
+        })
+    }
+
     #[test]
     fn new_window() -> Result<()> {
         let session = testing_session()?;
@@ -320,6 +410,75 @@ mod tests {
         Ok(())
     }
 
+    fn window_index(window: &Window) -> Result<u32> {
+        Ok(window
+            .target()
+            .targeted_command("display-message")?
+            .args(["-p", "#{window_index}"])
+            .execute()?
+            .trim()
+            .parse()?)
+    }
+
+    #[test]
+    fn new_window_position() -> Result<()> {
+        let session = testing_session()?;
+        let first = Window::builder(&session).build()?;
+        let _last = Window::builder(&session).build()?;
+        first.select()?;
+
+        let after = Window::builder(&session).after().build()?;
+        assert_eq!(window_index(&after)?, window_index(&first)? + 1);
+
+        first.select()?;
+        let first_index = window_index(&first)?;
+        let before = Window::builder(&session).before().build()?;
+        assert_eq!(window_index(&before)?, first_index);
+        assert_eq!(window_index(&first)?, first_index + 1);
+
+        let indexed = Window::builder(&session).index(42).build()?;
+        assert_eq!(window_index(&indexed)?, 42);
+        let _ = Window::builder(&session)
+            .index(42)
+            .build()
+            .expect_err("index should be taken");
+        Ok(())
+    }
+
+    #[test]
+    fn relative_root() -> Result<()> {
+        let root = env::temp_dir();
+        fs::create_dir_all(root.join("__celeris_testing_root"))?;
+        let session = Session::builder(TESTING_SESSION.to_owned())
+            .root(root.clone())?
+            .build()?;
+        let window = Window::builder(&session)
+            .root(PathBuf::from("__celeris_testing_root"))?
+            .build()?;
+        let output = window
+            .target()
+            .targeted_command("display-message")?
+            .args(["-p", "#{pane_current_path}"])
+            .execute()?;
+        assert_eq!(
+            root.join("__celeris_testing_root").to_string_lossy(),
+            output.trim()
+        );
+
+        let err = Window::builder(&session)
+            .root(PathBuf::from("__celeris_missing_root"))
+            .expect_err("a missing relative root should fail");
+        assert!(
+            format!("{err:?}").contains(
+                &root
+                    .join("__celeris_missing_root")
+                    .to_string_lossy()
+                    .into_owned()
+            )
+        );
+        Ok(())
+    }
+
     #[test]
     fn root_inheritance() -> Result<()> {
         let root = env::temp_dir();
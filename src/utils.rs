@@ -1,5 +1,7 @@
 use color_eyre::eyre::{Context, OptionExt};
+use color_eyre::owo_colors::OwoColorize;
 use color_eyre::{self, Result};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 pub fn file_name(path: &Path) -> Result<String> {
@@ -11,15 +13,6 @@ pub fn file_name(path: &Path) -> Result<String> {
         .to_owned())
 }
 
-pub fn file_stem(path: &Path) -> Result<String> {
-    Ok(path
-        .file_stem()
-        .ok_or_eyre(format!("{path:?} has no file stem"))?
-        .to_str()
-        .ok_or_eyre(format!("invalid utf08 encoding path: {path:?}"))?
-        .to_owned())
-}
-
 pub fn path_to_string(path: &Path) -> Result<String> {
     Ok(path
         .to_str()
@@ -36,19 +29,133 @@ pub fn shorten_path(path: PathBuf) -> PathBuf {
     }
 }
 
-pub fn expand_path(path: &Path) -> Result<PathBuf> {
-    let path = if path.starts_with("~") {
-        let home = dirs::home_dir()
-            .ok_or_eyre("home directory not found despite home shell expansion used")
-            .wrap_err("failed to expand ~ sign")?;
-        let stripped_path = path.strip_prefix("~").wrap_err("failed to expand ~ sign")?;
-        home.join(stripped_path)
-    } else {
-        path.to_owned()
-    };
+// Only a leading `~` is replaced, the path isn't resolved in any way
+pub fn expand_home(path: &Path) -> Result<PathBuf> {
+    if !path.starts_with("~") {
+        return Ok(path.to_owned());
+    }
+    let home = dirs::home_dir()
+        .ok_or_eyre("home directory not found despite home shell expansion used")
+        .wrap_err("failed to expand ~ sign")?;
+    let stripped_path = path.strip_prefix("~").wrap_err("failed to expand ~ sign")?;
+    Ok(home.join(stripped_path))
+}
 
+pub fn expand_path(path: &Path) -> Result<PathBuf> {
+    let path = expand_home(path)?;
     let path = path
         .canonicalize()
         .wrap_err(format!("failed to expand path: {path:?}"))?;
     Ok(path)
 }
+
+/// Expands a glob pattern into the directories it matches, a leading `~/` is the home directory.
+/// Lines without any glob syntax are passed through untouched so that missing paths still error
+/// out. Matches which aren't directories and patterns matching nothing are reported to `warnings`
+pub fn expand_glob(line: &str, warnings: &mut dyn Write) -> Result<Vec<PathBuf>> {
+    expand_glob_in(line, dirs::home_dir(), warnings)
+}
+
+fn expand_glob_in(
+    line: &str,
+    home: Option<PathBuf>,
+    warnings: &mut dyn Write,
+) -> Result<Vec<PathBuf>> {
+    if glob::Pattern::escape(line) == line {
+        return Ok(vec![PathBuf::from(line)]);
+    }
+
+    let pattern = match line.strip_prefix("~/") {
+        Some(stripped) => {
+            let home = home.ok_or_eyre("failed to expand ~ sign: home not found")?;
+            format!(
+                "{}/{stripped}",
+                glob::Pattern::escape(&home.to_string_lossy())
+            )
+        }
+        None => line.to_owned(),
+    };
+
+    let mut paths = Vec::new();
+    for path in glob::glob(&pattern).wrap_err(format!("invalid glob pattern: {line}"))? {
+        let path = path.wrap_err(format!("failed to expand glob pattern: {line}"))?;
+        if path.is_dir() {
+            paths.push(path);
+        } else {
+            writeln!(
+                warnings,
+                "{}: skipping {path:?}, not a directory",
+                "warning".yellow().bold()
+            )?;
+        }
+    }
+
+    if paths.is_empty() {
+        writeln!(
+            warnings,
+            "{}: no directories matched the pattern: {line}",
+            "warning".yellow().bold()
+        )?;
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn glob_dir(name: &str) -> Result<PathBuf> {
+        let dir = env::temp_dir().join(name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(dir.join("repo1"))?;
+        fs::create_dir_all(dir.join("repo2"))?;
+        fs::write(dir.join("notes.txt"), "")?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn expand_glob_home() -> Result<()> {
+        let home = glob_dir("__celeris_glob_home")?;
+        let mut warnings = Vec::new();
+        let paths = expand_glob_in("~/repo*", Some(home.clone()), &mut warnings)?;
+        assert_eq!(paths, [home.join("repo1"), home.join("repo2")]);
+        assert!(warnings.is_empty());
+
+        let _ = expand_glob_in("~/repo*", None, &mut warnings)
+            .expect_err("~ can't be expanded without a home directory");
+        Ok(())
+    }
+
+    #[test]
+    fn expand_glob_skips_files() -> Result<()> {
+        let dir = glob_dir("__celeris_glob_files")?;
+        let mut warnings = Vec::new();
+        let paths = expand_glob(&format!("{}/*", dir.display()), &mut warnings)?;
+        assert_eq!(paths, [dir.join("repo1"), dir.join("repo2")]);
+        let warnings = String::from_utf8(warnings)?;
+        assert!(warnings.contains("notes.txt"), "{warnings}");
+        assert!(warnings.contains("not a directory"), "{warnings}");
+        Ok(())
+    }
+
+    #[test]
+    fn expand_glob_no_match() -> Result<()> {
+        let dir = glob_dir("__celeris_glob_no_match")?;
+        let mut warnings = Vec::new();
+        let pattern = format!("{}/missing*", dir.display());
+        assert!(expand_glob(&pattern, &mut warnings)?.is_empty());
+        let warnings = String::from_utf8(warnings)?;
+        assert!(warnings.contains("no directories matched"), "{warnings}");
+
+        // without glob syntax the path is kept as is
+        let mut warnings = Vec::new();
+        let path = dir.join("missing");
+        let paths = expand_glob(&path.display().to_string(), &mut warnings)?;
+        assert_eq!(paths, [path]);
+        assert!(warnings.is_empty());
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
 #[allow(dead_code)]
 mod common;
 use std::fs;
+use std::path::Path;
 
-use celeris::Config;
+use celeris::{CONFIG_VERSION, Config, SearchRoot};
 use color_eyre::{Result, eyre::Context};
 
 use common::TestDirectoryManager;
@@ -41,3 +42,260 @@ fn validation() -> Result<()> {
         .expect_err("config should detect a search root is a file and not a directory");
     Ok(())
 }
+
+#[test]
+fn parse_error_backup() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let config_path = dir_mgr.config_dir().join("config.toml");
+    let config = "depth = 3\neditor = \n";
+    fs::write(&config_path, config).wrap_err("failed to write test config")?;
+
+    let error = Config::new(dir_mgr.as_ref()).expect_err("malformed config should fail to parse");
+    let error = error.to_string();
+    assert!(error.contains("line 2"), "{error}");
+    assert!(error.contains("config.toml.bak"), "{error}");
+
+    let backup_path = dir_mgr.config_dir().join("config.toml.bak");
+    assert_eq!(fs::read_to_string(backup_path)?, config);
+    assert_eq!(fs::read_to_string(config_path)?, config);
+    Ok(())
+}
+
+#[test]
+fn migration() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let config_path = dir_mgr.config_dir().join("config.toml");
+    // v0, from before versioning
+    let config = "# my editor\neditor = \"nano\"\ndepth = 3 # deep enough\n";
+    fs::write(&config_path, config).wrap_err("failed to write test config")?;
+
+    let migrated = Config::new(dir_mgr.as_ref())?;
+    assert_eq!(migrated.version, CONFIG_VERSION);
+    assert_eq!(migrated.depth, 3);
+    assert_eq!(migrated.editor.as_deref(), Some("nano"));
+
+    let backup_path = dir_mgr.config_dir().join("config.toml.v0.bak");
+    assert_eq!(fs::read_to_string(backup_path)?, config);
+    let rewritten: toml::Table = toml::from_str(&fs::read_to_string(&config_path)?)?;
+    assert_eq!(
+        rewritten.get("version").and_then(toml::Value::as_integer),
+        Some(CONFIG_VERSION.into())
+    );
+    assert_eq!(
+        rewritten.get("depth").and_then(toml::Value::as_integer),
+        Some(3)
+    );
+    // new fields get written out with their defaults
+    assert!(rewritten.contains_key("nested_layouts"));
+    let rewritten = fs::read_to_string(&config_path)?;
+    assert!(rewritten.starts_with("# my editor\neditor = \"nano\"\ndepth = 3 # deep enough\n"));
+
+    // current configs are left alone
+    let current = fs::read_to_string(&config_path)?;
+    let _ = Config::new(dir_mgr.as_ref())?;
+    assert_eq!(fs::read_to_string(&config_path)?, current);
+    Ok(())
+}
+
+#[test]
+fn set() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let config_path = dir_mgr.config_dir().join("config.toml");
+    let config = format!(
+        "version = {CONFIG_VERSION}\n# search settings\ndepth = 3 # deep enough\nexcludes = [\"_deps\"]\n"
+    );
+    fs::write(&config_path, config).wrap_err("failed to write test config")?;
+
+    Config::set(dir_mgr.as_ref(), "depth", "5")?;
+    Config::set(dir_mgr.as_ref(), "editor", "nvim")?;
+    Config::set(dir_mgr.as_ref(), "search_subdirs", "true")?;
+    assert_eq!(
+        fs::read_to_string(&config_path)?,
+        format!(
+            "version = {CONFIG_VERSION}\n# search settings\ndepth = 5 # deep enough\nexcludes = [\"_deps\"]\neditor = \"nvim\"\nsearch_subdirs = true\n"
+        )
+    );
+    let config = Config::new(dir_mgr.as_ref())?;
+    assert_eq!(config.depth, 5);
+    assert_eq!(config.editor.as_deref(), Some("nvim"));
+    assert!(config.search_subdirs);
+
+    let before = fs::read_to_string(&config_path)?;
+    let _ = Config::set(dir_mgr.as_ref(), "dept", "5").expect_err("unknown key should fail");
+    let _ = Config::set(dir_mgr.as_ref(), "version", "0").expect_err("version can't be set");
+    let _ = Config::set(dir_mgr.as_ref(), "depth", "deep").expect_err("wrong type should fail");
+    assert_eq!(fs::read_to_string(&config_path)?, before);
+
+    // outdated configs are edited as they are, migrating is left to the next start
+    fs::write(&config_path, "depth = 3\n").wrap_err("failed to write test config")?;
+    Config::set(dir_mgr.as_ref(), "depth", "4")?;
+    assert_eq!(fs::read_to_string(&config_path)?, "depth = 4\n");
+    assert!(!dir_mgr.config_dir().join("config.toml.v0.bak").exists());
+
+    // a missing config starts out as the default one
+    fs::remove_file(&config_path)?;
+    Config::set(dir_mgr.as_ref(), "depth", "4")?;
+    let config = Config::new(dir_mgr.as_ref())?;
+    assert_eq!(config.version, CONFIG_VERSION);
+    assert_eq!(config.depth, 4);
+    Ok(())
+}
+
+#[test]
+fn get() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let config_path = Config::path(dir_mgr.as_ref());
+    assert_eq!(config_path, dir_mgr.config_dir().join("config.toml"));
+    let config = format!(
+        "version = {CONFIG_VERSION}\ndepth = 3\neditor = \"nvim\"\nexcludes = [\"_deps\"]\n"
+    );
+    fs::write(&config_path, config).wrap_err("failed to write test config")?;
+
+    let get = |key| Config::get(dir_mgr.as_ref(), key);
+    assert_eq!(get("depth")?.as_deref(), Some("3"));
+    assert_eq!(get("editor")?.as_deref(), Some("nvim"));
+    assert_eq!(get("excludes")?.as_deref(), Some(r#"["_deps"]"#));
+    // options missing from the file aren't set, even though they have a default
+    assert_eq!(get("search_subdirs")?, None);
+    assert_eq!(get("layout_log")?, None);
+    let _ = get("dept").expect_err("unknown key should fail");
+
+    fs::remove_file(&config_path)?;
+    assert_eq!(get("depth")?, None);
+    Ok(())
+}
+
+#[test]
+fn edit() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let config_path = Config::path(dir_mgr.as_ref());
+    // `true` exits right away without touching the file
+    let config = format!("version = {CONFIG_VERSION}\neditor = \"true\"\n");
+    fs::write(&config_path, &config).wrap_err("failed to write test config")?;
+    Config::edit(dir_mgr.as_ref())?;
+    assert_eq!(fs::read_to_string(&config_path)?, config);
+
+    // neither backed up nor migrated, that's left to the next start
+    let config = "depth = 3\neditor = \"true\"\n";
+    fs::write(&config_path, config).wrap_err("failed to write test config")?;
+    Config::edit(dir_mgr.as_ref())?;
+    assert_eq!(fs::read_to_string(&config_path)?, config);
+    assert!(!dir_mgr.config_dir().join("config.toml.v0.bak").exists());
+
+    let config = "depth = 3\neditor = \n";
+    fs::write(&config_path, config).wrap_err("failed to write test config")?;
+    let error = Config::parse(&config_path).expect_err("malformed config should fail to parse");
+    assert!(error.to_string().contains("line 2"), "{error}");
+    assert!(!dir_mgr.config_dir().join("config.toml.bak").exists());
+    Ok(())
+}
+
+#[test]
+fn search_roots() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let config_path = Config::path(dir_mgr.as_ref());
+    let repo_dir = dir_mgr.repo_dir().canonicalize()?;
+    let layouts_dir = dir_mgr.layouts_dir().canonicalize()?;
+    let config = format!(
+        "version = {CONFIG_VERSION}\n\n# my repos\n[[search_roots]]\npath = \"{}\" # main one\n",
+        repo_dir.to_string_lossy()
+    );
+    fs::write(&config_path, &config).wrap_err("failed to write test config")?;
+
+    let root = |path: &Path| SearchRoot {
+        path: path.to_string_lossy().into_owned(),
+        depth: Some(2),
+        excludes: Some(vec!["_deps".to_owned()]),
+        ..Default::default()
+    };
+    Config::add_search_root(dir_mgr.as_ref(), root(&layouts_dir))?;
+    let rewritten = fs::read_to_string(&config_path)?;
+    assert!(rewritten.starts_with(&config), "{rewritten}");
+    let roots = Config::new(dir_mgr.as_ref())?.search_roots;
+    assert_eq!(roots.len(), 2);
+    assert_eq!(roots[1].path, layouts_dir.to_string_lossy());
+    assert_eq!(roots[1].depth, Some(2));
+    assert_eq!(roots[1].excludes, Some(vec!["_deps".to_owned()]));
+
+    let _ = Config::add_search_root(dir_mgr.as_ref(), root(&repo_dir))
+        .expect_err("duplicate root should fail");
+    let _ = Config::add_search_root(dir_mgr.as_ref(), root(&config_path))
+        .expect_err("file shouldn't be accepted as a root");
+    let _ = Config::add_search_root(dir_mgr.as_ref(), root(&repo_dir.join("doesntexist")))
+        .expect_err("missing directory shouldn't be accepted as a root");
+
+    Config::remove_search_root(dir_mgr.as_ref(), &layouts_dir)?;
+    assert_eq!(fs::read_to_string(&config_path)?, config);
+    let _ = Config::remove_search_root(dir_mgr.as_ref(), &layouts_dir)
+        .expect_err("removing a missing root should fail");
+
+    // inline arrays work too
+    let config = format!(
+        "version = {CONFIG_VERSION}\nsearch_roots = [{{ path = \"{}\" }}]\n",
+        repo_dir.to_string_lossy()
+    );
+    fs::write(&config_path, &config).wrap_err("failed to write test config")?;
+    Config::add_search_root(dir_mgr.as_ref(), root(&layouts_dir))?;
+    assert_eq!(Config::new(dir_mgr.as_ref())?.search_roots.len(), 2);
+    Config::remove_search_root(dir_mgr.as_ref(), &repo_dir)?;
+    let roots = Config::new(dir_mgr.as_ref())?.search_roots;
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].path, layouts_dir.to_string_lossy());
+    Ok(())
+}
+
+#[test]
+fn layered() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let config_path = dir_mgr.config_dir().join("config.toml");
+    let config = r#"
+    depth = 3
+    editor = "nano"
+    excludes = ["global"]
+    "#;
+    fs::write(&config_path, config).wrap_err("failed to write test config")?;
+
+    let project = dir_mgr.repo_dir().join("project");
+    let nested = project.join("src").join("nested");
+    fs::create_dir_all(&nested)?;
+
+    // no local config, global values are used
+    let config = Config::load_layered(dir_mgr.as_ref(), &nested)?;
+    assert_eq!(config.depth, 3);
+    assert_eq!(config.editor.as_deref(), Some("nano"));
+
+    let local_config = r#"
+    depth = 5
+    disable_template = true
+    "#;
+    fs::write(project.join(".celeris.toml"), local_config)
+        .wrap_err("failed to write local test config")?;
+    let config = Config::load_layered(dir_mgr.as_ref(), &nested)?;
+    assert_eq!(config.depth, 5);
+    assert!(config.disable_template);
+    assert_eq!(config.editor.as_deref(), Some("nano"));
+    assert_eq!(config.excludes, vec!["global".to_owned()]);
+
+    // the nearest local config wins
+    fs::write(nested.join(".celeris.toml"), "depth = 7")
+        .wrap_err("failed to write nested local test config")?;
+    let config = Config::load_layered(dir_mgr.as_ref(), &nested)?;
+    assert_eq!(config.depth, 7);
+    assert!(!config.disable_template);
+
+    // options that run or write something stay with the main config, typos aren't ignored either
+    [
+        "editor = \"sh -c 'echo pwned'\"",
+        "layout_log = \"/tmp/log\"",
+        "dept = 7",
+    ]
+    .into_iter()
+    .try_for_each(|local_config| -> Result<()> {
+        fs::write(nested.join(".celeris.toml"), local_config)
+            .wrap_err("failed to write nested local test config")?;
+        let _ = Config::load_layered(dir_mgr.as_ref(), &nested)
+            .expect_err("local config with an option it can't set should be rejected");
+        Ok(())
+    })?;
+    Ok(())
+}
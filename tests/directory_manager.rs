@@ -0,0 +1,58 @@
+use celeris::DirectoryManager;
+use color_eyre::Result;
+use std::{env, fs, path::PathBuf};
+
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        Self(env::temp_dir().join(name))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn custom_dirs_strict() -> Result<()> {
+    let testing_dir = TempDir::new("celeris_test_dir_mgr_strict");
+    assert!(
+        DirectoryManager::builder()
+            .config_dir(testing_dir.0.join("config"))
+            .is_err(),
+        "non existing config dir should be rejected"
+    );
+    assert!(
+        DirectoryManager::builder()
+            .cache_dir(testing_dir.0.join("cache"))
+            .is_err(),
+        "non existing cache dir should be rejected"
+    );
+    assert!(!testing_dir.0.exists());
+    Ok(())
+}
+
+#[test]
+fn custom_dirs_create() -> Result<()> {
+    let testing_dir = TempDir::new("celeris_test_dir_mgr_create");
+    let config_dir = testing_dir.0.join("nested").join("config");
+    let cache_dir = testing_dir.0.join("nested").join("cache");
+    let dir_mgr = DirectoryManager::builder()
+        .config_dir_create(config_dir.clone())?
+        .cache_dir_create(cache_dir.clone())?
+        .build()?;
+
+    assert!(config_dir.is_dir());
+    assert!(cache_dir.is_dir());
+    assert!(dir_mgr.layouts_dir().is_dir());
+
+    // already existing directories are fine too
+    let _ = DirectoryManager::builder()
+        .config_dir_create(config_dir)?
+        .cache_dir_create(cache_dir)?
+        .build()?;
+    Ok(())
+}
@@ -8,7 +8,11 @@ use color_eyre::eyre::Context;
 use git2::Repository;
 use itertools::Itertools;
 use std::iter;
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
 use std::{fs, path::PathBuf};
 
 fn create_repos(root: &Path, dirs: &[String]) -> Result<()> {
@@ -34,8 +38,7 @@ fn basic_search() -> Result<()> {
 
     let search_root = SearchRoot {
         path: dir_mgr.repo_dir().to_string_lossy().to_string(),
-        depth: None,
-        excludes: None,
+        ..Default::default()
     };
 
     let targets = ["test1", "test21", "test-123_"]
@@ -93,8 +96,7 @@ fn search_nested() -> Result<()> {
 
     let search_root = SearchRoot {
         path: dir_mgr.repo_dir().to_string_lossy().to_string(),
-        depth: None,
-        excludes: None,
+        ..Default::default()
     };
 
     let repo_names = ["sadfqwer", "foo", "bar"]
@@ -140,7 +142,7 @@ fn custom_depth() -> Result<()> {
     let search_root = SearchRoot {
         path: repo_root.to_string_lossy().to_string(),
         depth: Some(1),
-        excludes: None,
+        ..Default::default()
     };
 
     let config_custom_depth = basic_config(search_root.clone());
@@ -189,8 +191,7 @@ fn search_subdirs() -> Result<()> {
 
     let search_root = SearchRoot {
         path: repo_root.to_string_lossy().to_string(),
-        depth: None,
-        excludes: None,
+        ..Default::default()
     };
 
     let config = basic_config(search_root.clone());
@@ -217,14 +218,67 @@ fn search_subdirs() -> Result<()> {
     Ok(())
 }
 
+// {root is a repo} x {search_subdirs} x {depth}, a root which is a repo is always a result itself
+#[test]
+fn root_is_repo() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let repos = ["test1", "test2"]
+        .into_iter()
+        .map(ToOwned::to_owned)
+        .collect_vec();
+    let repo_root = dir_mgr.repo_dir().join("nested");
+    let plain_root = dir_mgr.repo_dir().join("plain");
+    [&repo_root, &plain_root]
+        .into_iter()
+        .try_for_each(|root| -> Result<()> {
+            fs::create_dir(root)?;
+            create_repos(root, &repos)?;
+            Ok(())
+        })?;
+    Repository::init(&repo_root)?;
+
+    let search = |root: &Path, search_subdirs: bool, depth: Option<usize>| -> Result<Vec<String>> {
+        let config = Config {
+            search_subdirs,
+            ..basic_config(SearchRoot {
+                path: root.to_string_lossy().into_owned(),
+                depth,
+                ..Default::default()
+            })
+        };
+        Ok(celeris::search(&config)?
+            .into_iter()
+            .map(PathBuf::from)
+            .map(|repo| repo.file_name().unwrap().to_str().unwrap().to_owned())
+            .sorted()
+            .collect_vec())
+    };
+
+    let all = ["nested", "test1", "test2"].map(ToOwned::to_owned).to_vec();
+    for depth in [None, Some(0), Some(1)] {
+        assert_eq!(search(&repo_root, false, depth)?, ["nested"], "{depth:?}");
+    }
+    assert_eq!(search(&repo_root, true, Some(0))?, ["nested"]);
+    assert_eq!(search(&repo_root, true, Some(1))?, all);
+    assert_eq!(search(&repo_root, true, None)?, all);
+
+    // search_subdirs only matters once a repo is found
+    for search_subdirs in [false, true] {
+        assert!(search(&plain_root, search_subdirs, Some(0))?.is_empty());
+        assert_eq!(search(&plain_root, search_subdirs, Some(1))?, repos);
+        assert_eq!(search(&plain_root, search_subdirs, None)?, repos);
+    }
+    Ok(())
+}
+
 #[test]
 fn excludes() -> Result<()> {
     let dir_mgr = TestDirectoryManager::new()?;
 
     let search_root = SearchRoot {
         path: dir_mgr.repo_dir().to_string_lossy().to_string(),
-        depth: None,
         excludes: Some(vec!["test21".to_owned()]),
+        ..Default::default()
     };
 
     let targets = ["test1", "test21", "test-123_"]
@@ -253,8 +307,7 @@ fn excludes() -> Result<()> {
 
     let search_root = SearchRoot {
         path: dir_mgr.repo_dir().to_string_lossy().to_string(),
-        depth: None,
-        excludes: None,
+        ..Default::default()
     };
 
     let config = basic_config(search_root);
@@ -279,8 +332,8 @@ fn excludes() -> Result<()> {
 
     let search_root = SearchRoot {
         path: dir_mgr.repo_dir().to_string_lossy().to_string(),
-        depth: None,
         excludes: Some(vec!["test21".to_owned()]),
+        ..Default::default()
     };
 
     let config = basic_config(search_root);
@@ -297,3 +350,214 @@ fn excludes() -> Result<()> {
     assert_eq!(results, vec!["test-123_"]);
     Ok(())
 }
+
+#[test]
+fn corrupt_recent_repos() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let search_root = SearchRoot {
+        path: dir_mgr.repo_dir().to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    create_repos(Path::new(&search_root.path), &["test1".to_owned()])?;
+    let recent_path = dir_mgr.as_ref().cache_dir().join("recent_repos");
+    let backup_path = dir_mgr.as_ref().cache_dir().join("recent_repos.bak");
+    let corrupt = "/some/repo\nnot/absolute";
+    fs::write(&recent_path, corrupt)?;
+
+    let config = Config {
+        recent_first: true,
+        strict_cache: true,
+        ..basic_config(search_root)
+    };
+    let error = celeris::search_with_recent(&config, dir_mgr.as_ref())
+        .expect_err("strict mode should fail on a corrupt cache");
+    assert!(format!("{error:#}").contains("line 2"), "{error:#}");
+    assert!(!backup_path.exists());
+
+    let config = Config {
+        strict_cache: false,
+        ..config
+    };
+    assert_eq!(
+        celeris::search_with_recent(&config, dir_mgr.as_ref())?.len(),
+        1
+    );
+    assert_eq!(fs::read_to_string(backup_path)?, corrupt);
+    assert!(!recent_path.exists());
+    Ok(())
+}
+
+#[test]
+fn zoxide() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let walked = dir_mgr.repo_dir().join("walked");
+    let zoxide = dir_mgr.repo_dir().join("zoxide");
+    fs::create_dir(&walked)?;
+    fs::create_dir(&zoxide)?;
+    create_repos(&walked, &["test1".to_owned()])?;
+    create_repos(&zoxide, &["test2", "excluded"].map(ToOwned::to_owned))?;
+    fs::create_dir(zoxide.join("not_a_repo"))?;
+
+    // stands in for zoxide, listing a repo already found in the search root as well
+    let listed = [
+        zoxide.join("test2"),
+        zoxide.join("excluded"),
+        zoxide.join("not_a_repo"),
+        walked.join("test1"),
+    ]
+    .iter()
+    .map(|dir| dir.to_string_lossy().to_string())
+    .join("\n");
+    let bin_dir = dir_mgr.repo_dir().join("bin");
+    fs::create_dir(&bin_dir)?;
+    let zoxide_bin = bin_dir.join("zoxide");
+    fs::write(
+        &zoxide_bin,
+        format!("#!/bin/sh\ncat <<'EOF'\n{listed}\nEOF\n"),
+    )?;
+    fs::set_permissions(&zoxide_bin, fs::Permissions::from_mode(0o755))?;
+
+    let config = Config {
+        excludes: vec!["excluded".to_owned()],
+        use_zoxide: true,
+        zoxide_path: Some(zoxide_bin),
+        ..basic_config(SearchRoot {
+            path: walked.to_string_lossy().to_string(),
+            ..Default::default()
+        })
+    };
+    let results = celeris::search(&config);
+    let results = results?
+        .into_iter()
+        .map(PathBuf::from)
+        .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+        .collect_vec();
+    assert_eq!(results, vec!["test1", "test2"]);
+    Ok(())
+}
+
+#[test]
+fn max_results() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let roots = ["first", "second"].map(|root| dir_mgr.repo_dir().join(root));
+    roots.iter().try_for_each(|root| -> Result<()> {
+        fs::create_dir(root)?;
+        create_repos(root, &["test1", "test2", "test3"].map(ToOwned::to_owned))
+    })?;
+
+    let config = Config {
+        search_roots: roots
+            .iter()
+            .map(|root| SearchRoot {
+                path: root.to_string_lossy().to_string(),
+                ..Default::default()
+            })
+            .collect(),
+        ..Config::default()
+    };
+    assert_eq!(celeris::search(&config)?.len(), 6);
+
+    // the cap applies across roots
+    [2, 4].into_iter().try_for_each(|max| -> Result<()> {
+        let config = Config {
+            max_results: Some(max),
+            ..config.clone()
+        };
+        assert_eq!(celeris::search(&config)?.len(), max);
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[test]
+fn streaming() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let search_root = SearchRoot {
+        path: dir_mgr.repo_dir().to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    create_repos(
+        Path::new(&search_root.path),
+        &["test1", "test2", "test3"].map(ToOwned::to_owned),
+    )?;
+    let config = basic_config(search_root);
+
+    let (sender, receiver) = mpsc::channel();
+    let streaming_config = config.clone();
+    let searcher = thread::spawn(move || {
+        celeris::search_streaming(&streaming_config, &AtomicBool::new(false), |repo| {
+            sender
+                .send(repo)
+                .expect("receiver should outlive the search")
+        })
+    });
+    let streamed = receiver.into_iter().sorted().collect_vec();
+    searcher.join().expect("search thread panicked")?;
+
+    let batch = celeris::search(&config)?.into_iter().sorted().collect_vec();
+    assert_eq!(streamed.len(), 3);
+    assert_eq!(streamed, batch);
+    Ok(())
+}
+
+#[test]
+fn streaming_cancelled() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let search_root = SearchRoot {
+        path: dir_mgr.repo_dir().to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    create_repos(
+        Path::new(&search_root.path),
+        &["test1", "test2", "test3"].map(ToOwned::to_owned),
+    )?;
+    let config = basic_config(search_root);
+
+    // a receiver that went away, e.g. the picker was closed, cancels the search
+    let (sender, receiver) = mpsc::channel::<String>();
+    drop(receiver);
+    let cancelled = AtomicBool::new(false);
+    let mut found = 0;
+    celeris::search_streaming(&config, &cancelled, |repo| {
+        found += 1;
+        if sender.send(repo).is_err() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    })?;
+    assert_eq!(found, 1);
+
+    let mut found = 0;
+    celeris::search_streaming(&config, &AtomicBool::new(true), |_| found += 1)?;
+    assert_eq!(found, 0);
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn search_async() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let search_root = SearchRoot {
+        path: dir_mgr.repo_dir().to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    create_repos(
+        Path::new(&search_root.path),
+        &["test1", "test2", "test3"].map(ToOwned::to_owned),
+    )?;
+    let config = basic_config(search_root);
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let found = runtime.block_on(async {
+        let mut search = celeris::search_async(config.clone());
+        let mut found = Vec::new();
+        while let Some(repo) = search.next().await {
+            found.push(repo);
+        }
+        search.finish().await.map(|_| found)
+    })?;
+
+    let batch = celeris::search(&config)?.into_iter().sorted().collect_vec();
+    assert_eq!(found.into_iter().sorted().collect_vec(), batch);
+    assert_eq!(batch.len(), 3);
+    Ok(())
+}
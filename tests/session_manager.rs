@@ -1,9 +1,9 @@
 #[allow(dead_code)]
 mod common;
 
-use celeris::Config;
-use celeris::tmux::Session;
-use celeris::{CreateSessionOptions, SwitchTarget};
+use celeris::tmux::{self, Session, TmuxExecutor, with_executor};
+use celeris::{Config, NameStyle, SearchRoot};
+use celeris::{CreateSessionOptions, CreatedSession, SwitchTarget};
 use celeris::{ListSessionsOptions, SessionManager};
 use color_eyre::eyre::eyre;
 use color_eyre::{Result, eyre::Context};
@@ -13,7 +13,10 @@ use itertools::Itertools;
 use rust_embed::Embed;
 use serde::Serialize;
 use std::fs::File;
-use std::path::Path;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex, mpsc};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -54,6 +57,8 @@ fn list_sessions() -> Result<()> {
         include_active: false,
         exclude_running: true,
         only_running: false,
+        count: false,
+        null: false,
     };
     let output = session_manager.list(opts)?;
     output
@@ -76,13 +81,15 @@ fn only_running() -> Result<()> {
         .iter()
         .map(|name| Session::builder((*name).to_owned()))
         .map(|mut builder| builder.build())
-        .collect::<Result<Vec<_>>>()?;
+        .collect::<Result<Vec<_>, _>>()?;
 
     let opts = ListSessionsOptions {
         tmux_format: false,
         include_active: false,
         exclude_running: false,
         only_running: true,
+        count: false,
+        null: false,
     };
     session_manager
         .list(opts)?
@@ -93,6 +100,50 @@ fn only_running() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn list_layouts() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let dummy_layouts = ["test1", "test2", "test3"];
+    common::create_dummy_layouts(&dummy_layouts, dir_mgr.as_ref())?;
+    let session_manager = common::test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    let layouts = session_manager
+        .list_layouts()
+        .into_iter()
+        .sorted()
+        .collect_vec();
+    assert_eq!(layouts, dummy_layouts);
+    Ok(())
+}
+
+#[test]
+fn reload() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let mut session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    assert!(!session_manager.layout_exists("external"));
+
+    fs::write(dir_mgr.layouts_dir().join("external.lua"), "")?;
+    assert!(!session_manager.layout_exists("external"));
+    session_manager.reload()?;
+    assert!(session_manager.layout_exists("external"));
+    Ok(())
+}
+
+#[test]
+fn hand_named_layout() -> Result<()> {
+    unsafe {
+        env::set_var("CELERIS_TMUX_SOCKET_NAME", "__celeris_testing");
+    }
+    let dir_mgr = TestDirectoryManager::new()?;
+    let template = TestFiles::get("generic_layout.lua").unwrap().data;
+    fs::write(dir_mgr.layouts_dir().join("host:8080.lua"), template)?;
+    let session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+
+    // tmux would replace `:` in the session name, so it's mapped like in names given on creation
+    assert_eq!(session_manager.list_layouts(), ["host꞉8080"]);
+    session_manager.switch(SwitchTarget::Session("host:8080".to_owned()), None)?;
+    Ok(())
+}
+
 #[test]
 fn list_sessions_active() -> Result<()> {
     unsafe {
@@ -115,7 +166,7 @@ fn list_sessions_active() -> Result<()> {
             name: Some(layout.to_owned()),
             path: env::temp_dir(),
             disable_editor: true,
-            machine_readable: false,
+            ..Default::default()
         })?;
         Ok(())
     })?;
@@ -128,15 +179,17 @@ fn list_sessions_active() -> Result<()> {
         )
     })?;
 
-    active_layouts
-        .iter()
-        .try_for_each(|layout| session_manager.switch(SwitchTarget::Session(layout.to_owned())))?;
+    active_layouts.iter().try_for_each(|layout| {
+        session_manager.switch(SwitchTarget::Session(layout.to_owned()), None)
+    })?;
 
     let opts = ListSessionsOptions {
         tmux_format: false,
         include_active: true,
         exclude_running: false,
         only_running: false,
+        count: false,
+        null: false,
     };
 
     let output = session_manager.list(opts)?;
@@ -181,6 +234,8 @@ fn remove_session() -> Result<()> {
             include_active: false,
             exclude_running: true,
             only_running: false,
+            count: false,
+            null: false,
         })?
     );
     assert!(layout_paths[0].exists());
@@ -209,10 +264,8 @@ fn create_session() -> Result<()> {
 
     thread::spawn(move || {
         let opts = CreateSessionOptions {
-            disable_editor: false,
-            name: None,
             path: layout_path,
-            machine_readable: false,
+            ..Default::default()
         };
         let result = session_manager.lock().unwrap().create(opts);
         err_tx.send(result).unwrap();
@@ -229,7 +282,8 @@ fn create_session() -> Result<()> {
         if result.is_ok() {
             return Err(result
                 .unwrap()
-                .expect_err("non error value sent through channel"));
+                .expect_err("non error value sent through channel")
+                .into());
         }
         thread::sleep(Duration::from_millis(50));
     }
@@ -252,12 +306,19 @@ fn create_session_default_template() -> Result<()> {
         disable_editor: true,
         path: layout_data.session_root.to_owned(),
         name: Some("test".to_owned()),
-        machine_readable: false,
+        ..Default::default()
     };
     let mut session_manager = SessionManager::new(config, Arc::clone(dir_mgr.inner()))?;
-    session_manager.create(opts.clone())?;
+    let created = session_manager.create(opts.clone())?;
 
     let layout_path = dir_mgr.layouts_dir().join("test").with_extension("lua");
+    assert_eq!(
+        created,
+        CreatedSession {
+            name: "test".to_owned(),
+            path: layout_path.clone(),
+        }
+    );
     let template = fs::read_to_string(&layout_path)?;
     assert!(template.is_empty());
     session_manager.remove(vec!["test".to_owned()])?;
@@ -271,6 +332,369 @@ fn create_session_default_template() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn create_session_template_overrides() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let custom_template = TestFiles::get("generic_layout.lua").unwrap().data;
+    fs::write(&dir_mgr.custom_template_path()?, &custom_template)?;
+    let layout_data = TestData {
+        session_root: &env::temp_dir(),
+        session_name: "test",
+    };
+    let layout_path = dir_mgr.layouts_dir().join("test").with_extension("lua");
+    let opts = CreateSessionOptions {
+        disable_editor: true,
+        path: layout_data.session_root.to_owned(),
+        name: Some("test".to_owned()),
+        empty: true,
+        ..Default::default()
+    };
+    let mut session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    session_manager.create(opts.clone())?;
+    assert!(fs::read_to_string(&layout_path)?.is_empty());
+    session_manager.remove(vec!["test".to_owned()])?;
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_embed_templates_with_extension::<DefaultTemplate>(".lua")?;
+    session_manager.create(CreateSessionOptions {
+        empty: false,
+        default_template: true,
+        ..opts.clone()
+    })?;
+    assert_eq!(
+        fs::read_to_string(&layout_path)?,
+        handlebars.render("default", &layout_data)?
+    );
+    session_manager.remove(vec!["test".to_owned()])?;
+
+    // without overrides the custom one is still used
+    session_manager.create(CreateSessionOptions {
+        empty: false,
+        ..opts
+    })?;
+    assert_eq!(fs::read(&layout_path)?, custom_template.as_ref());
+    Ok(())
+}
+
+#[test]
+fn create_session_template_helpers() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let session_root = dir_mgr.repo_dir().join("root");
+    fs::create_dir(&session_root)?;
+    fs::write(
+        &dir_mgr.custom_template_path()?,
+        "-- {{uppercase session_name}} in {{basename session_root}}\n",
+    )?;
+    let opts = CreateSessionOptions {
+        disable_editor: true,
+        path: session_root,
+        name: Some("test".to_owned()),
+        ..Default::default()
+    };
+    let mut session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    let created = session_manager.create(opts.clone())?;
+    assert_eq!(fs::read_to_string(&created.path)?, "-- TEST in root\n");
+    session_manager.remove(vec!["test".to_owned()])?;
+
+    fs::write(
+        &dir_mgr.custom_template_path()?,
+        "--\n{{#if session_name}}\n",
+    )?;
+    let error = session_manager
+        .create(opts)
+        .expect_err("an unclosed block should fail to render");
+    let message = format!("{error:?}");
+    assert!(message.contains("template.lua"), "{message}");
+    assert!(message.contains("line 3"), "{message}");
+    Ok(())
+}
+
+#[test]
+fn create_session_root_template() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    fs::write(&dir_mgr.custom_template_path()?, "-- global\n")?;
+    fs::write(dir_mgr.config_dir().join("code.lua"), "-- code\n")?;
+    let nested_template = dir_mgr.repo_dir().join("nested.lua");
+    fs::write(&nested_template, "-- nested {{session_name}}\n")?;
+    let code = dir_mgr.repo_dir().join("code");
+    let nested = code.join("nested");
+    [
+        code.join("project"),
+        nested.join("project"),
+        dir_mgr.repo_dir().join("other"),
+    ]
+    .iter()
+    .try_for_each(fs::create_dir_all)?;
+    let root = |path: &Path, template: String| SearchRoot {
+        path: path.to_string_lossy().into_owned(),
+        template: Some(template),
+        ..Default::default()
+    };
+    let config = Config {
+        search_roots: vec![
+            root(&code, "code.lua".to_owned()),
+            root(&nested, nested_template.to_string_lossy().into_owned()),
+        ],
+        ..Config::default()
+    };
+    let mut session_manager = SessionManager::new(Arc::new(config), Arc::clone(dir_mgr.inner()))?;
+    let mut create = |path: PathBuf, name: &str| -> Result<String> {
+        let created = session_manager.create(CreateSessionOptions {
+            disable_editor: true,
+            path,
+            name: Some(name.to_owned()),
+            ..Default::default()
+        })?;
+        Ok(fs::read_to_string(created.path)?)
+    };
+
+    // a relative template is looked up in the config directory, even if the current one has it
+    let cwd = env::current_dir()?;
+    env::set_current_dir(dir_mgr.repo_dir())?;
+    fs::write(dir_mgr.repo_dir().join("code.lua"), "-- cwd\n")?;
+    let created = create(code.join("project"), "code");
+    env::set_current_dir(cwd)?;
+    assert_eq!(created?, "-- code\n");
+    // the most specific root wins
+    assert_eq!(
+        create(nested.join("project"), "nested")?,
+        "-- nested nested\n"
+    );
+    assert_eq!(
+        create(dir_mgr.repo_dir().join("other"), "other")?,
+        "-- global\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn find_repo() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let repo_dir = dir_mgr.repo_dir().canonicalize()?;
+    let repos = ["a/project", "b/project", "c/unique"].map(|repo| repo_dir.join(repo));
+    repos.iter().try_for_each(|repo| -> Result<()> {
+        git2::Repository::init(repo)?;
+        Ok(())
+    })?;
+    let config = Config {
+        search_roots: vec![SearchRoot {
+            path: repo_dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        }],
+        ..Config::default()
+    };
+    let session_manager = SessionManager::new(Arc::new(config), Arc::clone(dir_mgr.inner()))?;
+
+    assert_eq!(session_manager.find_repo("unique")?, repos[2]);
+    let error = session_manager
+        .find_repo("project")
+        .expect_err("the name is ambiguous");
+    assert!(matches!(error, celeris::Error::AmbiguousRepo(..)));
+    let message = error.to_string();
+    assert!(message.contains(&repos[0].to_string_lossy().into_owned()));
+    assert!(message.contains(&repos[1].to_string_lossy().into_owned()));
+    // the path as printed by search picks one of them
+    let path = repos[1].to_string_lossy().into_owned();
+    assert_eq!(session_manager.find_repo(&path)?, repos[1]);
+    let error = session_manager
+        .find_repo("missing")
+        .expect_err("there is no such repo");
+    assert!(matches!(error, celeris::Error::RepoNotFound(name) if name == "missing"));
+    Ok(())
+}
+
+#[test]
+fn prefixed_repos() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let repo_dir = dir_mgr.repo_dir().canonicalize()?;
+    let repos = ["work/api", "personal/api"].map(|repo| repo_dir.join(repo));
+    repos.iter().try_for_each(|repo| -> Result<()> {
+        git2::Repository::init(repo)?;
+        Ok(())
+    })?;
+    let root = |path: PathBuf, prefix: &str| SearchRoot {
+        path: path.to_string_lossy().into_owned(),
+        prefix: Some(prefix.to_owned()),
+        ..Default::default()
+    };
+    let config = Config {
+        search_roots: vec![
+            root(repo_dir.join("work"), "work"),
+            root(repo_dir.join("personal"), "personal"),
+        ],
+        ..Config::default()
+    };
+    let paths = repos
+        .iter()
+        .map(|repo| repo.to_string_lossy().into_owned())
+        .collect_vec();
+    assert_eq!(
+        celeris::format_repos(&config, &paths),
+        [
+            format!("work:api     -- {}", paths[0]),
+            format!("personal:api -- {}", paths[1]),
+        ]
+    );
+    let mut session_manager = SessionManager::new(Arc::new(config), Arc::clone(dir_mgr.inner()))?;
+
+    assert_eq!(session_manager.find_repo("work:api")?, repos[0]);
+    assert_eq!(session_manager.find_repo("personal:api")?, repos[1]);
+    let names = repos
+        .iter()
+        .map(|repo| -> Result<String> {
+            let created = session_manager.create(CreateSessionOptions {
+                disable_editor: true,
+                path: repo.clone(),
+                empty: true,
+                ..Default::default()
+            })?;
+            Ok(created.name)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // ':' is substituted in tmux names
+    assert_eq!(names, ["work꞉api", "personal꞉api"]);
+    Ok(())
+}
+
+#[test]
+fn name_style() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let repo_dir = dir_mgr.repo_dir().canonicalize()?;
+    let repos = ["work/api", "personal/api"].map(|repo| repo_dir.join(repo));
+    repos.iter().try_for_each(|repo| -> Result<()> {
+        git2::Repository::init(repo)?;
+        Ok(())
+    })?;
+    let config = |name_style| Config {
+        search_roots: vec![SearchRoot {
+            path: repo_dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        }],
+        name_style,
+        ..Config::default()
+    };
+    let paths = repos
+        .iter()
+        .map(|repo| repo.to_string_lossy().into_owned())
+        .collect_vec();
+    let full = |path: &str| {
+        celeris::format_repos(&config(NameStyle::Full), &[path.to_owned()])[0]
+            .split_once(" -- ")
+            .map(|(name, _)| name.trim().to_owned())
+    };
+    assert!(full(&paths[0]).is_some_and(|name| name.ends_with("work/api")));
+
+    let mut session_manager = SessionManager::new(
+        Arc::new(config(NameStyle::Relative)),
+        Arc::clone(dir_mgr.inner()),
+    )?;
+    assert_eq!(session_manager.find_repo("personal/api")?, repos[1]);
+    let names = repos
+        .iter()
+        .map(|repo| -> Result<String> {
+            let created = session_manager.create(CreateSessionOptions {
+                disable_editor: true,
+                path: repo.clone(),
+                empty: true,
+                ..Default::default()
+            })?;
+            Ok(created.name)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(names, ["work/api", "personal/api"]);
+    Ok(())
+}
+
+#[test]
+fn recent_first() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let repo_dir = dir_mgr.repo_dir().canonicalize()?;
+    let repos = ["recent_a", "recent_b", "recent_c", "recent_d"].map(|name| repo_dir.join(name));
+    repos.iter().try_for_each(|repo| -> Result<()> {
+        git2::Repository::init(repo)?;
+        Ok(())
+    })?;
+
+    let mut session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    // picked twice, so it should only show up once
+    [&repos[2], &repos[0], &repos[2]]
+        .into_iter()
+        .enumerate()
+        .try_for_each(|(i, repo)| -> Result<()> {
+            session_manager.create(CreateSessionOptions {
+                disable_editor: true,
+                path: repo.to_owned(),
+                name: Some(format!("recent_{i}")),
+                ..Default::default()
+            })?;
+            Ok(())
+        })?;
+
+    let config = Config {
+        search_roots: vec![SearchRoot {
+            path: repo_dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        }],
+        recent_first: true,
+        ..Config::default()
+    };
+    let found = celeris::search_with_recent(&config, dir_mgr.as_ref())?;
+    let found = found
+        .iter()
+        .map(|repo| Path::new(repo).file_name())
+        .collect_vec();
+    assert_eq!(found.len(), repos.len());
+    assert_eq!(found[0], repos[2].file_name());
+    assert_eq!(found[1], repos[0].file_name());
+    Ok(())
+}
+
+#[test]
+fn rank() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let repo_dir = dir_mgr.repo_dir().canonicalize()?;
+    let repos = ["rank_a", "rank_b", "rank_c"].map(|name| repo_dir.join(name));
+    repos.iter().try_for_each(|repo| -> Result<()> {
+        git2::Repository::init(repo)?;
+        Ok(())
+    })?;
+
+    let mut session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    // the most recent one is picked only once, so the one picked twice should beat it
+    [&repos[1], &repos[1], &repos[0]]
+        .into_iter()
+        .enumerate()
+        .try_for_each(|(i, repo)| -> Result<()> {
+            session_manager.create(CreateSessionOptions {
+                disable_editor: true,
+                path: repo.to_owned(),
+                name: Some(format!("rank_{i}")),
+                ..Default::default()
+            })?;
+            Ok(())
+        })?;
+
+    let config = Config {
+        search_roots: vec![SearchRoot {
+            path: repo_dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        }],
+        rank: true,
+        // rank takes precedence
+        recent_first: true,
+        ..Config::default()
+    };
+    let found = celeris::search_with_recent(&config, dir_mgr.as_ref())?;
+    let found = found
+        .iter()
+        .map(|repo| Path::new(repo).file_name())
+        .collect_vec();
+    assert_eq!(found.len(), repos.len());
+    assert_eq!(found[0], repos[1].file_name());
+    assert_eq!(found[1], repos[0].file_name());
+    Ok(())
+}
+
 #[test]
 fn create_session_custom_template() -> Result<()> {
     let dir_mgr = TestDirectoryManager::new()?;
@@ -286,7 +710,7 @@ fn create_session_custom_template() -> Result<()> {
         path: env::temp_dir(),
         name: Some("test".to_owned()),
         disable_editor: true,
-        machine_readable: false,
+        ..Default::default()
     };
 
     let mut session_manager = SessionManager::new(config, Arc::clone(dir_mgr.inner()))?;
@@ -325,7 +749,16 @@ fn create_all() -> Result<()> {
     paths
         .iter()
         .try_for_each(|path| -> Result<()> { Ok(fs::create_dir(path)?) })?;
-    session_manager.create_all(paths.clone())?;
+    let mut messages = Vec::new();
+    session_manager.create_all(paths.clone(), |message| messages.push(message.to_owned()))?;
+    assert_eq!(
+        messages,
+        vec![
+            "created 1/3: test1",
+            "created 2/3: test2",
+            "created 3/3: test3"
+        ]
+    );
     paths
         .into_iter()
         .map(|path| path.with_extension("lua"))
@@ -345,7 +778,7 @@ fn create_all() -> Result<()> {
         .for_each(|path| assert!(!path.exists()));
 
     let _ = session_manager
-        .create_all(paths.clone())
+        .create_all(paths.clone(), |_| {})
         .expect_err("create-all should fail with duplicate file names");
     paths
         .into_iter()
@@ -354,6 +787,110 @@ fn create_all() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn create_all_edit_all() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    // records each invocation on its own line, the extra word checks that the editor is split
+    let editor_path = dir_mgr.repo_dir().join("editor");
+    let invocations = dir_mgr.repo_dir().join("invocations");
+    fs::write(
+        &editor_path,
+        format!("#!/bin/sh\necho \"$@\" >> {}\n", invocations.display()),
+    )?;
+    fs::set_permissions(&editor_path, fs::Permissions::from_mode(0o755))?;
+    let config = Config {
+        editor: Some(format!("{} --wait", editor_path.display())),
+        ..Config::default()
+    };
+    let mut session_manager = SessionManager::new(Arc::new(config), Arc::clone(dir_mgr.inner()))?;
+
+    let paths = vec![
+        dir_mgr.layouts_dir().join("test1"),
+        dir_mgr.layouts_dir().join("test2"),
+    ];
+    paths
+        .iter()
+        .try_for_each(|path| -> Result<()> { Ok(fs::create_dir(path)?) })?;
+    let created = session_manager.create_all(paths.clone(), |_| {})?;
+    assert_eq!(created, ["test1", "test2"]);
+    assert!(!invocations.exists());
+
+    session_manager.edit_all(&created)?;
+    let layout_paths = paths
+        .iter()
+        .map(|path| path.with_extension("lua").display().to_string())
+        .join(" ");
+    assert_eq!(
+        fs::read_to_string(&invocations)?,
+        format!("--wait {layout_paths}\n")
+    );
+    Ok(())
+}
+
+#[test]
+fn edit_non_blocking() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    common::create_dummy_layouts(&["test"], dir_mgr.as_ref())?;
+    // marks that the editor has been run only once the test releases it. It gives up waiting after
+    // a while so that an editor which is blocked on doesn't hang the test
+    let editor_path = dir_mgr.repo_dir().join("editor");
+    let edited = dir_mgr.repo_dir().join("edited");
+    let release = dir_mgr.repo_dir().join("release");
+    fs::write(
+        &editor_path,
+        format!(
+            "#!/bin/sh\ni=0\nwhile [ ! -e {} ] && [ $i -lt 500 ]; do sleep 0.01; i=$((i + 1)); done\ntouch {}\n",
+            release.display(),
+            edited.display()
+        ),
+    )?;
+    fs::set_permissions(&editor_path, fs::Permissions::from_mode(0o755))?;
+    let editor_config = |editor: String, editor_blocking: bool| Config {
+        editor: Some(editor),
+        editor_blocking,
+        ..Config::default()
+    };
+
+    let session_manager = SessionManager::new(
+        Arc::new(editor_config(editor_path.display().to_string(), false)),
+        Arc::clone(dir_mgr.inner()),
+    )?;
+    session_manager.edit("test")?;
+    assert!(!edited.exists());
+    fs::write(&release, "")?;
+    let start = Instant::now();
+    let wait_time = Duration::from_secs(5);
+    while !edited.exists() {
+        if start.elapsed() > wait_time {
+            return Err(eyre!("editor hasn't run after {wait_time:?}"));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    fs::remove_file(&edited)?;
+
+    // the editor stays released from now on, so the blocking runs return right away
+
+    // --wait asks to block even with blocking turned off
+    let session_manager = SessionManager::new(
+        Arc::new(editor_config(
+            format!("{} --wait", editor_path.display()),
+            false,
+        )),
+        Arc::clone(dir_mgr.inner()),
+    )?;
+    session_manager.edit("test")?;
+    assert!(edited.exists());
+    fs::remove_file(&edited)?;
+
+    let session_manager = SessionManager::new(
+        Arc::new(editor_config(editor_path.display().to_string(), true)),
+        Arc::clone(dir_mgr.inner()),
+    )?;
+    session_manager.edit("test")?;
+    assert!(edited.exists());
+    Ok(())
+}
+
 // shitty test
 #[test]
 fn last_session() -> Result<()> {
@@ -368,16 +905,49 @@ fn last_session() -> Result<()> {
         disable_editor: true,
         path: env::temp_dir(),
         name: Some("test".to_owned()),
-        machine_readable: false,
+        ..Default::default()
     };
 
     session_manager.create(opts)?;
-    let _ = session_manager
-        .switch(SwitchTarget::LastSession)
+    let error = session_manager
+        .switch(SwitchTarget::LastSession, None)
         .expect_err("switch should error out when there is no last session");
+    assert!(matches!(error, celeris::Error::NoLastSession));
+
+    session_manager.switch(SwitchTarget::Session("test".to_owned()), None)?;
+    session_manager.switch(SwitchTarget::LastSession, None)?;
+    Ok(())
+}
+
+#[test]
+fn switch_path() -> Result<()> {
+    unsafe {
+        env::set_var("CELERIS_TMUX_SOCKET_NAME", "__celeris_testing");
+    }
+    let dir_mgr = TestDirectoryManager::new()?;
+    let template = TestFiles::get("generic_layout.lua").unwrap().data;
+    fs::write(&dir_mgr.custom_template_path()?, template)?;
+    let path = dir_mgr.repo_dir().join("switch_path");
+    fs::create_dir(&path)?;
+    let mut session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
 
-    session_manager.switch(SwitchTarget::Session("test".to_owned()))?;
-    session_manager.switch(SwitchTarget::LastSession)?;
+    session_manager.switch_path(&path, None)?;
+    assert!(session_manager.layout_exists("switch_path"));
+
+    // the layout is reused instead of another one being created next to it
+    session_manager.switch_path(&path, None)?;
+    assert_eq!(fs::read_dir(dir_mgr.layouts_dir())?.count(), 1);
+
+    // same name, but the layout belongs to the other directory
+    let other = dir_mgr.repo_dir().join("other").join("switch_path");
+    fs::create_dir_all(&other)?;
+    let _ = session_manager
+        .switch_path(&other, None)
+        .expect_err("switch should error out on a layout created for another directory");
+
+    let _ = session_manager
+        .switch_path(&dir_mgr.repo_dir().join("missing"), None)
+        .expect_err("switch should error out on a path which doesn't exist");
     Ok(())
 }
 
@@ -396,6 +966,222 @@ fn comp_test() -> Result<()> {
     let layout_str = handlebars.render("comptest", &test_data)?;
     common::new_layout("comptest", &layout_str, dir_mgr.as_ref())?;
     let session_manager = common::test_session_manager(Arc::clone(dir_mgr.inner()))?;
-    session_manager.switch(SwitchTarget::Session("comptest".to_owned()))?;
+    session_manager.switch(SwitchTarget::Session("comptest".to_owned()), None)?;
+    Ok(())
+}
+
+#[test]
+fn broken_layout_error() -> Result<()> {
+    unsafe {
+        env::set_var("CELERIS_TMUX_SOCKET_NAME", "__celeris_testing");
+    }
+    let dir_mgr = TestDirectoryManager::new()?;
+    let layout = "local celeris = require(\"celeris\")\nceleris.does_not_exist()\n";
+    common::new_layout("broken", layout, dir_mgr.as_ref())?;
+    let session_manager = common::test_session_manager(Arc::clone(dir_mgr.inner()))?;
+
+    let error = session_manager
+        .switch(SwitchTarget::Session("broken".to_owned()), None)
+        .expect_err("broken layout should fail");
+    let error = format!("{error:?}");
+    assert!(error.contains("broken.lua"), "{error}");
+    assert!(error.contains("broken.lua:2:"), "{error}");
+    Ok(())
+}
+
+#[test]
+fn create_running_collision() -> Result<()> {
+    unsafe {
+        env::set_var("CELERIS_TMUX_SOCKET_NAME", "__celeris_testing");
+    }
+    let dir_mgr = TestDirectoryManager::new()?;
+    let mut session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    let name = "__celeris_testing_collision";
+    let _session = Session::builder(name.to_owned()).build()?;
+
+    let opts = CreateSessionOptions {
+        path: env::temp_dir(),
+        name: Some(name.to_owned()),
+        disable_editor: true,
+        strict: true,
+        ..Default::default()
+    };
+    let error = session_manager
+        .create(opts.clone())
+        .expect_err("strict create should fail when a running session has the same name");
+    assert!(matches!(error, celeris::Error::SessionRunning(_)));
+    assert!(
+        !dir_mgr
+            .layouts_dir()
+            .join(name)
+            .with_extension("lua")
+            .exists()
+    );
+
+    session_manager.create(CreateSessionOptions {
+        strict: false,
+        empty: false,
+        default_template: false,
+        ..opts
+    })?;
+    assert!(
+        dir_mgr
+            .layouts_dir()
+            .join(name)
+            .with_extension("lua")
+            .exists()
+    );
+    Ok(())
+}
+
+#[test]
+fn exists() -> Result<()> {
+    unsafe {
+        env::set_var("CELERIS_TMUX_SOCKET_NAME", "__celeris_testing");
+    }
+    let dir_mgr = TestDirectoryManager::new()?;
+    common::create_dummy_layouts(&["test1"], dir_mgr.as_ref())?;
+    let session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    assert!(session_manager.layout_exists("test1"));
+    assert!(!session_manager.layout_exists("test2"));
+
+    let name = "__celeris_testing_exists";
+    assert!(!session_manager.session_running(name)?);
+    let _session = Session::builder(name.to_owned()).build()?;
+    assert!(session_manager.session_running(name)?);
+    Ok(())
+}
+
+#[test]
+fn warm() -> Result<()> {
+    unsafe {
+        env::set_var("CELERIS_TMUX_SOCKET_NAME", "__celeris_testing");
+    }
+    let dir_mgr = TestDirectoryManager::new()?;
+    let marker = "__celeris_testing_warm_marker";
+    // the attach would fail outside of a terminal, the marker shows the layout ran past it
+    fs::write(
+        dir_mgr.layouts_dir().join("__celeris_testing_warm1.lua"),
+        format!(
+            r#"local celeris = require("celeris")
+            local session = celeris.Session.new({{}})
+            session:attach()
+            celeris.rawCommand({{ "new-session", "-d", "-s", "{marker}" }})"#
+        ),
+    )?;
+    fs::write(
+        dir_mgr.layouts_dir().join("__celeris_testing_warm2.lua"),
+        r#"error("running sessions shouldn't be warmed")"#,
+    )?;
+    let _running = Session::builder("__celeris_testing_warm2".to_owned()).build()?;
+    let session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+
+    session_manager.warm(Vec::new())?;
+    assert!(session_manager.session_running(marker)?);
+    Session::from(marker)?.kill()?;
+
+    let _ = session_manager
+        .warm(vec!["__celeris_testing_missing".to_owned()])
+        .expect_err("warming a missing layout should fail");
+    Ok(())
+}
+
+#[test]
+fn list_conflicting_options() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    let opts = ListSessionsOptions {
+        tmux_format: false,
+        include_active: false,
+        exclude_running: true,
+        only_running: true,
+        count: false,
+        null: false,
+    };
+    let _ = session_manager
+        .list(opts)
+        .expect_err("only_running and exclude_running should be mutually exclusive");
+    Ok(())
+}
+
+// Answers like tmux does when there's no server on its socket
+struct NoServer;
+
+impl TmuxExecutor for NoServer {
+    fn execute(&self, _: &mut Command) -> tmux::Result<String> {
+        Err(tmux::Error::ServerNotRunning)
+    }
+
+    fn succeeds(&self, _: &mut Command) -> tmux::Result<bool> {
+        Ok(false)
+    }
+}
+
+#[test]
+fn list_no_server() -> Result<()> {
+    let dir_mgr = TestDirectoryManager::new()?;
+    let dummy_layouts = ["test1", "test2", "test3"];
+    common::create_dummy_layouts(&dummy_layouts, dir_mgr.as_ref())?;
+    let session_manager = test_session_manager(Arc::clone(dir_mgr.inner()))?;
+    let list = |opts| with_executor(Rc::new(NoServer), || session_manager.list(opts));
+
+    let opts = ListSessionsOptions {
+        tmux_format: false,
+        include_active: false,
+        exclude_running: false,
+        only_running: true,
+        count: false,
+        null: false,
+    };
+    assert_eq!(list(opts)?, "");
+
+    let opts = ListSessionsOptions {
+        tmux_format: false,
+        include_active: false,
+        exclude_running: false,
+        only_running: false,
+        count: false,
+        null: false,
+    };
+    assert_eq!(list(opts)?.lines().collect_vec(), dummy_layouts);
+
+    let opts = ListSessionsOptions {
+        tmux_format: false,
+        include_active: false,
+        exclude_running: false,
+        only_running: false,
+        count: false,
+        null: true,
+    };
+    assert_eq!(list(opts)?, dummy_layouts.join("\0"));
+
+    let opts = ListSessionsOptions {
+        tmux_format: false,
+        include_active: false,
+        exclude_running: true,
+        only_running: false,
+        count: false,
+        null: false,
+    };
+    assert_eq!(list(opts)?.lines().collect_vec(), dummy_layouts);
+
+    let opts = ListSessionsOptions {
+        tmux_format: false,
+        include_active: false,
+        exclude_running: false,
+        only_running: false,
+        count: true,
+        null: false,
+    };
+    assert_eq!(list(opts)?, "3");
+    let opts = ListSessionsOptions {
+        tmux_format: false,
+        include_active: false,
+        exclude_running: false,
+        only_running: true,
+        count: true,
+        null: false,
+    };
+    assert_eq!(list(opts)?, "0");
     Ok(())
 }